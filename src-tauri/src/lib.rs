@@ -1,4 +1,5 @@
-use std::sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}};
+use std::sync::{Arc, Condvar, LazyLock, Mutex, OnceLock, RwLock, RwLockReadGuard, atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering}};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 
@@ -8,9 +9,349 @@ use tauri::{
 use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+mod logging;
+mod search;
+mod spaces;
+#[cfg(target_os = "linux")]
+mod wayland;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 struct Config {
     shortcut: String,
+    /// How captured windows are downscaled into thumbnails.
+    scaler: ThumbnailScaler,
+    /// Drop a trailing ` — <app name>` / ` - <app name>` from titles; the original stays in `raw_title`.
+    strip_app_suffix: bool,
+    /// Titles longer than this many characters are cut with an ellipsis (some apps put whole
+    /// paths or URLs in them); the full title stays in `raw_title` for search. Unset keeps them whole.
+    max_title_length: Option<usize>,
+    /// Enumerate windows once at launch to hide first-call latency. Off defers all window
+    /// access until the overlay is first opened.
+    warm_up_on_start: bool,
+    /// Read each window's `AXDocument` to fill `WindowInfo::document_path` (costs AX round-trips).
+    resolve_documents: bool,
+    /// Keep the overlay above other windows while shown.
+    always_on_top: bool,
+    /// Window level the overlay is kept at while `always_on_top` is on.
+    overlay_level: OverlayLevel,
+    /// Sample owner-process CPU/memory into `WindowInfo::cpu_percent` / `memory_mb`.
+    resource_stats: bool,
+    /// Resolve AX sheet → parent relationships into `WindowInfo::parent_id`.
+    resolve_parents: bool,
+    /// Pad every thumbnail to this `(width, height)` aspect ratio (e.g. `[16, 10]`); unset keeps
+    /// each window's own proportions.
+    thumbnail_aspect: Option<(u32, u32)>,
+    /// RGB fill for the padding added by `thumbnail_aspect`.
+    thumbnail_background: [u8; 3],
+    /// `refresh_windows_async` emits the list without capturing; the frontend asks for visible
+    /// tiles through `request_thumbnails_in_range`.
+    lazy_thumbnails: bool,
+    /// The first this many listed windows (the likeliest picks) are captured and emitted before
+    /// any of the rest start. 0 captures all in one pass.
+    priority_thumbnails: usize,
+    /// App names listed first, in this order; other apps follow in their usual order.
+    app_priority: Vec<String>,
+    /// Base order of the list, applied after `app_priority`.
+    sort_mode: SortMode,
+    /// Bundle id treated as "us" by the self-filter, in addition to our own pid. When unset,
+    /// the running build's id (detected at startup, never saved) is used, so dev builds and
+    /// helper processes are filtered too.
+    self_bundle_id: Option<String>,
+    /// On battery, at most this many thumbnails are captured at once and CG scaling drops to
+    /// low interpolation quality. Unset disables the adaptation.
+    thumbnail_cpu_budget: Option<usize>,
+    /// Drop windows a capture would not show (off-screen or fully transparent), judged from the
+    /// window descriptions without capturing.
+    require_capturable: bool,
+    /// Presses of a shortcut closer than this to its previous accepted press are ignored (key
+    /// bounce, accidental double-press). Each shortcut is debounced on its own.
+    shortcut_debounce_ms: u64,
+    /// Activating the same app again within this window skips the `open`/`osascript`
+    /// subprocesses and goes straight to the AX raise. 0 always spawns them.
+    activation_dedup_ms: u64,
+    /// `open`/`osascript` subprocesses still running after this long are killed (LaunchServices
+    /// can wedge and never return): activation continues with the AX raise, a launch fails.
+    open_timeout_ms: u64,
+    /// Hash every captured thumbnail (dHash) into `WindowInfo::phash` for `find_similar_windows`.
+    compute_phash: bool,
+    /// When a window's backing-resolution capture would exceed this many pixels on its longer
+    /// side, capture it at nominal (1x) resolution instead.
+    capture_source_max_dimension: Option<u32>,
+    /// How activation brings the chosen window forward once its app is frontmost.
+    raise_action: RaiseAction,
+    /// How long `peek_window` shows a window before focus returns to the overlay.
+    peek_duration_ms: u64,
+    /// Action name → combo, e.g. `activate_previous = "alt+tab"` or `activate_nth_1 = "alt+1"`.
+    /// `toggle` here overrides the legacy `shortcut` field, which remains its alias.
+    shortcuts: HashMap<String, String>,
+    /// Color space thumbnails are rendered into. Only affects how previews look, never which
+    /// window gets activated.
+    thumbnail_color_space: ThumbnailColorSpace,
+    /// Hand the frontend small, stable ids (a counter per stable key) instead of OS window ids.
+    /// The OS id stays internal and every command maps back to it; meant for frontend tests.
+    stable_display_ids: bool,
+    /// App name → how its windows are captured, for apps whose capture is known to fail
+    /// (GPU-composited browsers, games), so they are not re-probed on every refresh.
+    app_capture_overrides: HashMap<String, CaptureStrategy>,
+    /// While the overlay is open, poll listed windows' titles and emit `window:title-changed`.
+    live_titles: bool,
+    /// Which API captures thumbnails. `Auto` uses ScreenCaptureKit where available.
+    capture_backend: CaptureBackend,
+    /// Never embed thumbnails in listings, not even from the sync `list_windows`; they always
+    /// arrive as `window:thumbnail` events, keeping list payloads small.
+    stream_thumbnails_only: bool,
+    /// Capture a window only when it is activated (and once when first seen), serving that image
+    /// on later listings instead of re-capturing idle windows every time.
+    focus_time_thumbnails: bool,
+    /// Capture listing thumbnails narrower as the window count grows (500/300/180px).
+    adaptive_thumbnail_width: bool,
+    /// Also capture a small crop of each window's top-left corner (favicon, app badge) as
+    /// `WindowInfo::corner_glyph`, a quick visual key next to the title.
+    capture_corner_glyph: bool,
+    /// The corner crop, in image pixels from the window's top-left.
+    corner_glyph_crop: CropRect,
+    /// Whether the overlay is shown before or after it is moved onto the current workspace.
+    show_sequence: ShowSequence,
+    /// Fade the overlay in over this many milliseconds; 0 shows it at once.
+    fade_ms: u64,
+    /// Enable the `dump_ax_tree` debugging command.
+    enable_ax_dump: bool,
+    /// Enable `set_provider_mode`, for developing the frontend against mock data on a real build.
+    enable_provider_switch: bool,
+    /// Leave windows out of listings whose title fell back to the app name
+    /// (`WindowInfo::is_title_fallback`), mostly untitled helper windows.
+    hide_fallback_titles: bool,
+    /// Deliver a refresh's thumbnails as one binary bundle (see `encode_thumbnail_bundle`)
+    /// fetched over the `rifthold` URI scheme, instead of one event per window.
+    bundle_thumbnails: bool,
+    /// Always show the overlay on this display (an id from `get_displays`) instead of the
+    /// current monitor. Ignored while that display is unplugged.
+    overlay_display: Option<u32>,
+    /// Total thumbnail pixels of one listing, shared out evenly across its windows (overrides
+    /// `adaptive_thumbnail_width`). Unset keeps a fixed per-window width.
+    total_thumbnail_pixel_budget: Option<u64>,
+    /// Whether listings show every window or one representative per app.
+    group_mode: GroupMode,
+    /// Save the last listing (no thumbnails) on quit and show it, marked `stale`, as soon as
+    /// the next session starts, until its first real listing arrives.
+    persist_last_list: bool,
+    /// Owner names of system surfaces never listed, compared case-insensitively. Which ones
+    /// leak into the window list varies by macOS version.
+    system_app_exclusions: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            shortcut: "alt+space".into(),
+            scaler: ThumbnailScaler::default(),
+            strip_app_suffix: false,
+            max_title_length: Some(256),
+            warm_up_on_start: true,
+            resolve_documents: false,
+            always_on_top: true,
+            overlay_level: OverlayLevel::default(),
+            resource_stats: false,
+            resolve_parents: false,
+            thumbnail_aspect: None,
+            thumbnail_background: [0, 0, 0],
+            lazy_thumbnails: false,
+            priority_thumbnails: 6,
+            app_priority: Vec::new(),
+            sort_mode: SortMode::default(),
+            self_bundle_id: None,
+            thumbnail_cpu_budget: None,
+            require_capturable: false,
+            shortcut_debounce_ms: 150,
+            activation_dedup_ms: 1000,
+            open_timeout_ms: 2000,
+            compute_phash: false,
+            capture_source_max_dimension: None,
+            raise_action: RaiseAction::default(),
+            peek_duration_ms: 800,
+            shortcuts: HashMap::new(),
+            thumbnail_color_space: ThumbnailColorSpace::default(),
+            stable_display_ids: false,
+            app_capture_overrides: HashMap::new(),
+            live_titles: false,
+            capture_backend: CaptureBackend::default(),
+            stream_thumbnails_only: false,
+            focus_time_thumbnails: false,
+            adaptive_thumbnail_width: false,
+            capture_corner_glyph: false,
+            corner_glyph_crop: CropRect { x: 0.0, y: 0.0, width: 96.0, height: 96.0 },
+            show_sequence: ShowSequence::default(),
+            fade_ms: 0,
+            enable_ax_dump: false,
+            enable_provider_switch: false,
+            hide_fallback_titles: false,
+            bundle_thumbnails: false,
+            overlay_display: None,
+            total_thumbnail_pixel_budget: None,
+            group_mode: GroupMode::default(),
+            persist_last_list: false,
+            system_app_exclusions: ["Control Center", "Notification Center", "Spotlight", "Dock", "Window Server"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// `Srgb` renders every display alike; `DeviceRgb` is the raw device space (colors can shift
+/// on P3 panels); `DisplayNative` keeps the capturing display's own space.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ThumbnailColorSpace {
+    DeviceRgb,
+    #[default]
+    Srgb,
+    DisplayNative,
+}
+
+/// `Skip` shows the app icon without attempting a capture; `OnScreenComposite` grabs what is
+/// on screen inside the window's frame (the window and anything below it) instead of the
+/// window's own backing store.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum CaptureStrategy {
+    #[default]
+    Default,
+    Skip,
+    OnScreenComposite,
+}
+
+/// `Auto` captures through ScreenCaptureKit on macOS 14+ (where `SCScreenshotManager` exists)
+/// and `CGWindowListCreateImage` before that; the others force one API, for debugging.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum CaptureBackend {
+    #[default]
+    Auto,
+    CoreGraphics,
+    ScreenCaptureKit,
+}
+
+/// `ShowThenSize` shows the overlay where it last was and then moves it, which is visible
+/// as a jump when the workspace changed; `SizeThenShow` places it while still hidden.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ShowSequence {
+    SizeThenShow,
+    #[default]
+    ShowThenSize,
+}
+
+/// Window levels the overlay may be kept at. None is at or above the screen saver's level,
+/// so the overlay can never appear over the lock screen.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum OverlayLevel {
+    Normal,
+    #[default]
+    Floating,
+    ModalPanel,
+}
+
+impl OverlayLevel {
+    /// The `NSWindowLevel` value (`kCGNormalWindowLevel`, `kCGFloatingWindowLevel`,
+    /// `kCGModalPanelWindowLevel`).
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    fn ns_level(self) -> i64 {
+        match self {
+            OverlayLevel::Normal => 0,
+            OverlayLevel::Floating => 3,
+            OverlayLevel::ModalPanel => 8,
+        }
+    }
+}
+
+/// `ZOrder` keeps the provider's front-to-back order. `ActiveAppFirst` moves the other windows
+/// of the frontmost app to the top, for switching within the current app; the frontmost
+/// window itself and everything else follow in z-order.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum SortMode {
+    #[default]
+    ZOrder,
+    ActiveAppFirst,
+}
+
+/// What `invalidate` clears: captured thumbnails, the last listing, or the provider's window
+/// snapshot.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum CacheKind {
+    Thumbnails,
+    List,
+    Snapshot,
+    All,
+}
+
+/// `Windows` lists every window; `Apps` lists only each app's frontmost window, standing
+/// in for the rest (`WindowInfo::app_window_count`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum GroupMode {
+    #[default]
+    Windows,
+    Apps,
+}
+
+impl Config {
+    /// Combo of the overlay toggle: `shortcuts.toggle`, else the legacy `shortcut`.
+    fn toggle_shortcut(&self) -> &str {
+        self.shortcuts.get("toggle").unwrap_or(&self.shortcut)
+    }
+}
+
+/// `Raise` performs `AXRaise`; `Main` sets `AXMain`/`AXFocused`, which gives keyboard focus in
+/// apps where raising alone does not; `RaiseThenMain` does both.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum RaiseAction {
+    #[default]
+    Raise,
+    Main,
+    RaiseThenMain,
+}
+
+/// `CoreGraphics` draws into a scaled bitmap context (fast, macOS only); the other variants
+/// decode the full image and resize it with the matching `image` crate filter.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum ThumbnailScaler {
+    #[default]
+    CoreGraphics,
+    Lanczos3,
+    Triangle,
+    Nearest,
+}
+
+impl ThumbnailScaler {
+    /// The `image` filter for software scaling, or `None` for the CoreGraphics path.
+    fn filter(self) -> Option<image::imageops::FilterType> {
+        match self {
+            ThumbnailScaler::CoreGraphics => None,
+            ThumbnailScaler::Lanczos3 => Some(image::imageops::FilterType::Lanczos3),
+            ThumbnailScaler::Triangle => Some(image::imageops::FilterType::Triangle),
+            ThumbnailScaler::Nearest => Some(image::imageops::FilterType::Nearest),
+        }
+    }
+}
+
+/// Effective configuration, loaded once and updated through `update_config`.
+static CONFIG: LazyLock<RwLock<Config>> = LazyLock::new(|| RwLock::new(load_config()));
+
+fn config() -> RwLockReadGuard<'static, Config> {
+    CONFIG.read().unwrap()
+}
+
+/// Applies `change` to the in-memory config and persists the result.
+fn update_config(change: impl FnOnce(&mut Config)) -> Result<(), String> {
+    let mut config = CONFIG.write().unwrap();
+    change(&mut config);
+    save_config(&config)
 }
 
 fn config_path() -> PathBuf {
@@ -20,12 +361,141 @@ fn config_path() -> PathBuf {
         .join("config.toml")
 }
 
+/// Parse error of the config file found at startup, cleared by `repair_config`.
+static CONFIG_LOAD_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
 fn load_config() -> Config {
     if let Ok(content) = fs::read_to_string(config_path()) {
-        toml::from_str(&content).unwrap_or_else(|_| Config { shortcut: "alt+space".into() })
+        toml::from_str(&content).unwrap_or_else(|error| {
+            log::warn!("[rifthold] config file is corrupt, using defaults: {error}");
+            *CONFIG_LOAD_ERROR.lock().unwrap() = Some(error.to_string());
+            Config::default()
+        })
     } else {
-        Config { shortcut: "alt+space".into() }
+        Config::default()
+    }
+}
+
+/// Outcome of `repair_config`: keys kept from the old file, and keys found there that were
+/// invalid, unknown or unreadable and so fell back to their defaults.
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct ConfigRepairReport {
+    recovered: Vec<String>,
+    reset: Vec<String>,
+    /// Where the original file was copied to, if there was one.
+    backup_path: Option<String>,
+}
+
+/// Splits TOML into top-level items (one `key = value`, or a `[table]` with its body), each
+/// parseable on its own, so broken syntax only loses the items it appears in.
+fn toml_items(content: &str) -> Vec<String> {
+    let mut items: Vec<String> = Vec::new();
+    let mut in_table = false;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let starts_item = if trimmed.starts_with('[') {
+            in_table = true;
+            true
+        } else {
+            !in_table && !line.starts_with(char::is_whitespace) && trimmed.contains('=')
+        };
+        match items.last_mut() {
+            Some(item) if !starts_item => {
+                item.push('\n');
+                item.push_str(line);
+            }
+            _ => items.push(line.to_string()),
+        }
+    }
+    items
+}
+
+/// Name of the key or table an item defines, for the repair report.
+fn toml_item_name(item: &str) -> String {
+    let head = item.lines().next().unwrap_or_default().trim();
+    head.trim_start_matches('[')
+        .split([']', '='])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches('"')
+        .to_string()
+}
+
+/// Recovers every top-level key of `content` that still parses and is valid on its own.
+fn recover_config(content: &str) -> (Config, ConfigRepairReport) {
+    let mut report = ConfigRepairReport::default();
+    let mut parsed = toml::Table::new();
+    match content.parse::<toml::Table>() {
+        Ok(table) => parsed = table,
+        Err(_) => {
+            for item in toml_items(content) {
+                match item.parse::<toml::Table>() {
+                    Ok(table) => {
+                        for (key, value) in table {
+                            parsed.insert(key, value);
+                        }
+                    }
+                    Err(_) if item.trim().is_empty() || item.trim_start().starts_with('#') => {}
+                    Err(_) => report.reset.push(toml_item_name(&item)),
+                }
+            }
+        }
+    }
+
+    let mut valid = toml::Table::new();
+    for (key, value) in parsed {
+        let single = toml::Table::from_iter([(key.clone(), value.clone())]);
+        // Unknown keys deserialize fine but are dropped, so check the key survives a round trip.
+        let survives = toml::Value::Table(single)
+            .try_into::<Config>()
+            .ok()
+            .and_then(|config| toml::Value::try_from(config).ok())
+            .is_some_and(|table| table.get(&key).is_some());
+        if survives {
+            report.recovered.push(key.clone());
+            valid.insert(key, value);
+        } else {
+            report.reset.push(key);
+        }
+    }
+
+    let config = toml::Value::Table(valid).try_into().unwrap_or_default();
+    (config, report)
+}
+
+/// Rewrites the config file from whatever of it is still valid: the original is backed up to
+/// `config.toml.bak` first, and the recovered settings replace the in-memory config.
+#[tauri::command]
+fn repair_config() -> Result<ConfigRepairReport, CommandError> {
+    let path = config_path();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+        Err(error) => return Err(CommandError::new("config_read_failed", error.to_string())),
+    };
+
+    let (recovered, mut report) = match &content {
+        Some(content) => recover_config(content),
+        None => (Config::default(), ConfigRepairReport::default()),
+    };
+    if content.is_some() {
+        let backup = path.with_extension("toml.bak");
+        fs::copy(&path, &backup).map_err(|e| CommandError::new("config_backup_failed", e.to_string()))?;
+        report.backup_path = Some(backup.display().to_string());
     }
+
+    let mut current = CONFIG.write().unwrap();
+    *current = recovered;
+    save_config(&current).map_err(|e| CommandError::new("config_write_failed", e))?;
+    CONFIG_LOAD_ERROR.lock().unwrap().take();
+    log::info!(
+        "[rifthold] config repaired: recovered={:?} reset={:?}",
+        report.recovered,
+        report.reset
+    );
+    Ok(report)
 }
 
 fn save_config(config: &Config) -> Result<(), String> {
@@ -35,8 +505,304 @@ fn save_config(config: &Config) -> Result<(), String> {
     fs::write(path, content).map_err(|e| e.to_string())
 }
 
-#[derive(serde::Serialize, Clone)]
+/// Sub-rect of a captured window image, in image pixels with a top-left origin.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct CropRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl CropRect {
+    /// Clamps the rect to a `width`x`height` image; `None` if nothing of it remains.
+    fn clamped(self, width: f64, height: f64) -> Option<CropRect> {
+        let x = self.x.max(0.0);
+        let y = self.y.max(0.0);
+        let right = (self.x + self.width).min(width);
+        let bottom = (self.y + self.height).min(height);
+        if right - x < 1.0 || bottom - y < 1.0 {
+            return None;
+        }
+        Some(CropRect {
+            x: x.floor(),
+            y: y.floor(),
+            width: (right - x).floor(),
+            height: (bottom - y).floor(),
+        })
+    }
+}
+
+/// Target dimensions for a thumbnail no wider than `max_width`, preserving aspect ratio.
+fn thumbnail_size(width: u32, height: u32, max_width: u32) -> (u32, u32) {
+    if width > max_width {
+        let ratio = max_width as f32 / width as f32;
+        (max_width, ((height as f32 * ratio) as u32).max(1))
+    } else {
+        (width, height)
+    }
+}
+
+/// Software downscale shared by every provider that has raw RGBA pixels.
+fn resize_rgba(
+    image: &image::RgbaImage,
+    max_width: u32,
+    filter: image::imageops::FilterType,
+) -> image::RgbaImage {
+    let (width, height) = thumbnail_size(image.width(), image.height(), max_width);
+    if (width, height) == image.dimensions() {
+        return image.clone();
+    }
+    image::imageops::resize(image, width, height, filter)
+}
+
+/// Letterboxes/pillarboxes packed RGB8 pixels onto a centered canvas of the `aspect` ratio
+/// filled with `background`. Returns `None` when the image already has that ratio.
+fn pad_to_aspect(
+    rgb: &[u8],
+    width: u32,
+    height: u32,
+    aspect: (u32, u32),
+    background: [u8; 3],
+) -> Option<(Vec<u8>, u32, u32)> {
+    let (aspect_w, aspect_h) = (aspect.0.max(1) as u64, aspect.1.max(1) as u64);
+    let (w, h) = (width as u64, height as u64);
+    let (canvas_w, canvas_h) = if w * aspect_h > h * aspect_w {
+        (w, (w * aspect_h).div_ceil(aspect_w))
+    } else {
+        ((h * aspect_w).div_ceil(aspect_h), h)
+    };
+    if (canvas_w, canvas_h) == (w, h) {
+        return None;
+    }
+
+    let source = image::RgbImage::from_raw(width, height, rgb.to_vec())?;
+    let mut canvas = image::RgbImage::from_pixel(canvas_w as u32, canvas_h as u32, image::Rgb(background));
+    image::imageops::replace(&mut canvas, &source, ((canvas_w - w) / 2) as i64, ((canvas_h - h) / 2) as i64);
+    Some((canvas.into_raw(), canvas_w as u32, canvas_h as u32))
+}
+
+/// 64-bit difference hash: a 9x8 grayscale downscale, one bit per horizontal gradient sign.
+fn dhash(rgb: &[u8], width: u32, height: u32) -> Option<u64> {
+    let image = image::RgbImage::from_raw(width, height, rgb.to_vec())?;
+    let gray = image::DynamicImage::ImageRgb8(image)
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .into_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if gray.get_pixel(x, y)[0] < gray.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Some(hash)
+}
+
+/// Perceptual hash of each window's latest capture (`Config::compute_phash`).
+static WINDOW_PHASHES: LazyLock<Mutex<HashMap<String, u64>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Files the hash of a capture of `window_id`, if one was computed.
+fn record_phash(window_id: &str, phash: Option<u64>) {
+    if let Some(hash) = phash {
+        WINDOW_PHASHES.lock().unwrap().insert(window_id.to_string(), hash);
+    }
+}
+
+/// An encoded thumbnail (JPEG, or PNG for app icons) with the dHash of its pixels
+/// (`Config::compute_phash`). Kept as raw bytes so bundles skip the base64 round trip.
+#[derive(Clone)]
+struct EncodedThumbnail {
+    bytes: Vec<u8>,
+    png: bool,
+    phash: Option<u64>,
+    /// `WindowInfo::corner_glyph`, cropped from the same capture.
+    corner_glyph: Option<String>,
+}
+
+impl EncodedThumbnail {
+    fn data_url(&self) -> String {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let mime = if self.png { "image/png" } else { "image/jpeg" };
+        format!("data:{mime};base64,{}", general_purpose::STANDARD.encode(&self.bytes))
+    }
+}
+
+/// Encodes packed RGB8 pixels as JPEG, first padded to `aspect` with `background` if given.
+fn encode_jpeg(rgb: &[u8], width: u32, height: u32, aspect: Option<(u32, u32)>, background: [u8; 3]) -> Option<Vec<u8>> {
+    use image::ImageEncoder;
+
+    let padded = aspect.and_then(|aspect| pad_to_aspect(rgb, width, height, aspect, background));
+    let (rgb, width, height) = match &padded {
+        Some((pixels, w, h)) => (pixels.as_slice(), *w, *h),
+        None => (rgb, width, height),
+    };
+
+    let mut jpeg_data = Vec::with_capacity(rgb.len() / 4);
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_data, 80)
+        .write_image(rgb, width, height, image::ExtendedColorType::Rgb8)
+        .ok()?;
+    Some(jpeg_data)
+}
+
+/// Encodes packed RGB8 pixels as a JPEG thumbnail, padded to `Config::thumbnail_aspect` if set.
+fn encode_thumbnail(rgb: &[u8], width: u32, height: u32) -> Option<EncodedThumbnail> {
+    let (compute_phash, aspect, background) = {
+        let config = config();
+        (config.compute_phash, config.thumbnail_aspect, config.thumbnail_background)
+    };
+    let phash = if compute_phash { dhash(rgb, width, height) } else { None };
+    let bytes = encode_jpeg(rgb, width, height, aspect, background)?;
+    Some(EncodedThumbnail {
+        bytes,
+        png: false,
+        phash,
+        corner_glyph: None,
+    })
+}
+
+/// Drops the alpha channel and encodes the image as a JPEG thumbnail.
+fn encode_rgba_thumbnail(image: &image::RgbaImage) -> Option<EncodedThumbnail> {
+    let rgb = image::DynamicImage::ImageRgba8(image.clone()).into_rgb8();
+    encode_thumbnail(rgb.as_raw(), rgb.width(), rgb.height())
+}
+
+/// Poll interval of `status_with_timeout`.
+const SUBPROCESS_POLL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Runs `command` to completion like `Command::status`, but kills it once `timeout` has
+/// passed; `Ok(None)` then.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn status_with_timeout(
+    command: &mut std::process::Command,
+    timeout: std::time::Duration,
+) -> std::io::Result<Option<std::process::ExitStatus>> {
+    let mut child = command.spawn()?;
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if std::time::Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        std::thread::sleep(SUBPROCESS_POLL);
+    }
+}
+
+/// Runs FFI-heavy `body`, turning a panic into `default` so it cannot unwind through the
+/// CG/AX call stack or take down the backend. The panic message is logged by the hook.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn guard_ffi<T>(name: &str, default: T, body: impl FnOnce() -> T) -> T {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(body)) {
+        Ok(value) => value,
+        Err(_) => {
+            log::error!("[rifthold] {name} panicked; returning a safe default");
+            default
+        }
+    }
+}
+
+/// Error envelope returned by commands so the frontend can branch on `code`
+/// instead of parsing free-form messages.
+#[derive(Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
+pub struct CommandError {
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl CommandError {
+    fn new(code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            code: code.into(),
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new("internal", message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::new("internal", message)
+    }
+}
+
+/// Why a thumbnail capture produced nothing. Serialized (snake_case) as the `reason` of
+/// `window:thumbnail-failed`.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum CaptureError {
+    /// `CaptureStrategy::Skip` is configured and no app icon could stand in.
+    Skipped,
+    /// The window's description (bounds) could not be read; it is most likely gone.
+    WindowNotFound,
+    /// The capture API returned no image (missing permission, or a window it cannot capture).
+    NullImage,
+    /// The image came back zero pixels wide or high.
+    ZeroSize,
+    /// The bitmap context for scaling could not be created or has no pixel buffer.
+    ContextCreationFailed,
+    /// JPEG encoding failed.
+    EncodeFailed,
+    /// The window's display is asleep and no earlier capture is cached.
+    DisplayAsleep,
+    /// The window is off screen (another Space, minimized) and no earlier capture is cached.
+    OffScreen,
+    /// The capture panicked and was contained.
+    Panicked,
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::Skipped => "capture skipped for this app",
+            Self::WindowNotFound => "window description not found",
+            Self::NullImage => "capture returned no image",
+            Self::ZeroSize => "captured image is empty",
+            Self::ContextCreationFailed => "bitmap context creation failed",
+            Self::EncodeFailed => "JPEG encoding failed",
+            Self::DisplayAsleep => "display asleep, nothing cached",
+            Self::OffScreen => "window off screen, nothing cached",
+            Self::Panicked => "capture panicked",
+        };
+        f.write_str(reason)
+    }
+}
+
+impl From<tauri::Error> for CommandError {
+    fn from(error: tauri::Error) -> Self {
+        Self::new("tauri", error.to_string())
+    }
+}
+
+#[derive(serde::Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct WindowInfo {
     pub id: String,
     pub title: String,
@@ -44,208 +810,2548 @@ pub struct WindowInfo {
     pub is_title_fallback: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub thumbnail: Option<String>,
+    /// The thumbnail is a cached earlier capture (e.g. the window is on another Space).
+    pub thumbnail_stale: bool,
+    /// Per-app placeholder from the degraded listing path (window enumeration failed).
+    pub degraded: bool,
+    /// Marked as needing attention through `flag_window`.
+    pub flagged: bool,
+    /// Small crop of the window's top-left corner, when `Config::capture_corner_glyph` is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corner_glyph: Option<String>,
+    /// Identity that survives re-listing (owner + role + title). `id` is the OS handle used for
+    /// activation and may change when a window is recreated; `stable_key` is what pins, MRU and
+    /// selection should key on across refreshes.
+    pub stable_key: String,
+    /// Unmodified title when `title` was shortened for display; search should match against it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_title: Option<String>,
+    /// Window frame in global screen points (top-left origin), when the provider knows it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<WindowBounds>,
+    /// Filesystem path of the document shown in the window, for apps that expose `AXDocument`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document_path: Option<String>,
+    /// CPU usage of the owning process since the previous listing. Process-level: every window
+    /// of an app reports the same number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f64>,
+    /// Physical memory footprint of the owning process (whole app, not this window).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<f64>,
+    /// For sheets/modal dialogs: the id of the window they are attached to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// Set when another window of the same app has the same title: a short hint (document name,
+    /// position or ordinal) telling them apart. Title-based activation is ambiguous for these.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_disambiguator: Option<String>,
+    /// Display holding most of the window's area (see `get_displays`), for per-monitor layouts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_id: Option<u32>,
+    /// dHash of the latest thumbnail, when `Config::compute_phash` is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phash: Option<u64>,
+    /// Spans of `title` matched by the `list_windows` query, as UTF-16 `(start, end)` offsets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_ranges: Option<Vec<(usize, usize)>>,
+    /// In `GroupMode::Apps`, how many windows of its app this representative stands for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_window_count: Option<usize>,
+    /// From the previous session's persisted list (`Config::persist_last_list`): the window
+    /// may be gone, and the entry cannot be activated.
+    pub stale: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct WindowBounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl WindowBounds {
+    /// Same frame within `tolerance` points on every edge (AX and CG round differently).
+    fn approx_eq(&self, other: &WindowBounds, tolerance: f64) -> bool {
+        (self.x - other.x).abs() <= tolerance
+            && (self.y - other.y).abs() <= tolerance
+            && (self.width - other.width).abs() <= tolerance
+            && (self.height - other.height).abs() <= tolerance
+    }
+
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    fn intersection_area(&self, other: &WindowBounds) -> f64 {
+        let width = (self.x + self.width).min(other.x + other.width) - self.x.max(other.x);
+        let height = (self.y + self.height).min(other.y + other.height) - self.y.max(other.y);
+        width.max(0.0) * height.max(0.0)
+    }
+}
+
+/// An attached display; `bounds` share the global point space of `WindowBounds`.
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayInfo {
+    pub id: u32,
+    pub bounds: WindowBounds,
+    pub is_main: bool,
+}
+
+fn displays() -> Vec<DisplayInfo> {
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        return macos::displays();
+    }
+
+    // Matches the screen the mock windows are laid out on.
+    vec![DisplayInfo {
+        id: 1,
+        bounds: WindowBounds { x: 0.0, y: 0.0, width: 1920.0, height: 1080.0 },
+        is_main: true,
+    }]
+}
+
+/// The display overlapping `bounds` the most; `None` if it is entirely off every display.
+fn display_for_bounds(bounds: &WindowBounds, displays: &[DisplayInfo]) -> Option<u32> {
+    displays
+        .iter()
+        .map(|display| (display.id, display.bounds.intersection_area(bounds)))
+        .filter(|&(_, area)| area > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id)
+}
+
+/// Sets `display_id` from each window's bounds.
+fn assign_displays(windows: &mut [WindowInfo]) {
+    if windows.iter().all(|window| window.bounds.is_none()) {
+        return;
+    }
+    let displays = displays();
+    for window in windows.iter_mut() {
+        window.display_id = window.bounds.and_then(|bounds| display_for_bounds(&bounds, &displays));
+    }
+}
+
+/// Scripting-oriented view of a window for `export_windows_json`; no thumbnails.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedWindow<'a> {
+    id: &'a str,
+    title: &'a str,
+    app_name: &'a str,
+    bounds: Option<WindowBounds>,
+}
+
+/// Separators apps put between a document title and their own name.
+const TITLE_SUFFIX_SEPARATORS: [&str; 3] = [" — ", " – ", " - "];
+
+/// Returns `title` without a trailing `<separator><app_name>`, or `None` if there is no such suffix.
+fn strip_app_suffix(title: &str, app_name: &str) -> Option<String> {
+    if app_name.is_empty() {
+        return None;
+    }
+    let lower_title = title.to_lowercase();
+    let lower_app = app_name.to_lowercase();
+    TITLE_SUFFIX_SEPARATORS.iter().find_map(|separator| {
+        let suffix = format!("{separator}{lower_app}");
+        if !lower_title.ends_with(&suffix) || lower_title.len() != title.len() {
+            return None;
+        }
+        let stripped = title.get(..title.len() - suffix.len())?.trim_end();
+        (!stripped.is_empty()).then(|| stripped.to_string())
+    })
+}
+
+/// Provider-independent adjustments applied to every listing according to the config.
+fn apply_list_options(windows: &mut Vec<WindowInfo>, config: &Config) {
+    if config.hide_fallback_titles {
+        let before = windows.len();
+        windows.retain(|window| !window.is_title_fallback);
+        log::debug!("[rifthold] hid {} windows with fallback titles", before - windows.len());
+    }
+    if config.strip_app_suffix {
+        for window in windows.iter_mut() {
+            if let Some(stripped) = strip_app_suffix(&window.title, &window.app_name) {
+                window.raw_title = Some(std::mem::replace(&mut window.title, stripped));
+            }
+        }
+    }
+    if let Some(max_chars) = config.max_title_length {
+        for window in windows.iter_mut() {
+            if let Some(truncated) = truncate_title(&window.title, max_chars) {
+                let full = std::mem::replace(&mut window.title, truncated);
+                window.raw_title.get_or_insert(full);
+            }
+        }
+    }
+    disambiguate_titles(windows);
+    assign_displays(windows);
+    if config.compute_phash {
+        let phashes = WINDOW_PHASHES.lock().unwrap();
+        for window in windows.iter_mut() {
+            window.phash = phashes.get(&window.id).copied();
+        }
+    }
+    sort_windows(windows, config);
+}
+
+/// `title` cut to at most `max_chars` characters, the last being an ellipsis; `None` if it
+/// already fits. Counts chars, so multibyte titles are never split inside a character.
+fn truncate_title(title: &str, max_chars: usize) -> Option<String> {
+    if max_chars == 0 {
+        return None;
+    }
+    title.char_indices().nth(max_chars)?;
+    let end = title
+        .char_indices()
+        .nth(max_chars - 1)
+        .map_or(title.len(), |(index, _)| index);
+    Some(format!("{}…", title[..end].trim_end()))
+}
+
+/// Sort step of the listing. Windows of prioritized apps move to the front in priority order,
+/// then `Config::sort_mode` applies; the sorts are stable, so ties keep the provider's order.
+fn sort_windows(windows: &mut [WindowInfo], config: &Config) {
+    // Providers list front to back, so the first window is the one the user was just in.
+    let frontmost = windows.first().map(|window| (window.id.clone(), window.app_name.clone()));
+
+    if !config.app_priority.is_empty() {
+        let rank = |window: &WindowInfo| {
+            config
+                .app_priority
+                .iter()
+                .position(|app| app == &window.app_name)
+                .unwrap_or(usize::MAX)
+        };
+        windows.sort_by_key(rank);
+    }
+
+    if config.sort_mode == SortMode::ActiveAppFirst {
+        if let Some((front_id, front_app)) = frontmost {
+            windows.sort_by_key(|window| !(window.app_name == front_app && window.id != front_id));
+        }
+    }
+}
+
+/// Fills `title_disambiguator` on windows sharing an (app, title) pair, preferring the
+/// document name, then the window position, then the ordinal within the group.
+fn disambiguate_titles(windows: &mut [WindowInfo]) {
+    let mut groups: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+    for (index, window) in windows.iter().enumerate() {
+        groups
+            .entry((window.app_name.as_str(), window.title.as_str()))
+            .or_default()
+            .push(index);
+    }
+    let collisions: Vec<Vec<usize>> = groups.into_values().filter(|group| group.len() > 1).collect();
+
+    for group in collisions {
+        for (ordinal, &index) in group.iter().enumerate() {
+            let window = &windows[index];
+            let document_name = window
+                .document_path
+                .as_deref()
+                .and_then(|path| std::path::Path::new(path).file_name())
+                .map(|name| name.to_string_lossy().into_owned());
+            let hint = document_name
+                .or_else(|| window.bounds.map(|b| format!("at {:.0}, {:.0}", b.x, b.y)))
+                .unwrap_or_else(|| format!("#{}", ordinal + 1));
+            windows[index].title_disambiguator = Some(hint);
+        }
+    }
+}
+
+/// Builds the identity used for `WindowInfo::stable_key`. `owner` is the bundle id when known,
+/// otherwise the app name.
+fn stable_window_key(owner: &str, role: &str, title: &str) -> String {
+    format!("{owner}|{role}|{title}")
 }
 
 trait WindowProvider: Send + Sync {
     fn list(&self, capture_thumbnails: bool) -> Vec<WindowInfo>;
+    /// Lists only the windows owned by the application with `bundle_id`.
+    fn list_app(&self, bundle_id: &str, capture_thumbnails: bool) -> Vec<WindowInfo>;
     fn activate(&self, id: &str) -> Result<(), String>;
+    /// Activates the application owning window `id`, bringing all of its windows forward
+    /// without raising any one of them specifically.
+    fn activate_app(&self, id: &str) -> Result<(), String>;
     fn clear_cache(&self);
+    /// Drops thumbnails kept to stand in for windows that cannot be captured right now.
+    fn clear_thumbnail_cache(&self) {}
+    /// Drops the provider's window snapshot, so the next lookup re-enumerates.
+    fn clear_snapshot(&self) {}
+    /// Current frame of window `id` in global display coordinates.
+    fn window_bounds(&self, id: &str) -> Option<WindowBounds> {
+        self.list(false).into_iter().find(|window| window.id == id).and_then(|window| window.bounds)
+    }
+    /// Activates the app `bundle_id`, launching it if it is not running. Returns whether it
+    /// was already running.
+    fn launch_app(&self, _bundle_id: &str) -> Result<bool, String> {
+        Err("launching apps is not supported by this provider".into())
+    }
+    /// Moves and resizes window `id` to `frame` (global display coordinates).
+    fn set_window_frame(&self, _id: &str, _frame: WindowBounds) -> Result<(), String> {
+        Err("moving windows is not supported by this provider".into())
+    }
+    /// Accessibility hierarchy of window `id` (roles, titles, frames), for debugging.
+    fn ax_tree(&self, _id: &str) -> Result<serde_json::Value, String> {
+        Err("accessibility trees are not supported by this provider".into())
+    }
+    /// Current title of window `id`, read without a full listing. `None` if unknown.
+    fn window_title(&self, _id: &str) -> Option<String> {
+        None
+    }
+    /// Icon of the application owning window `id`, as a PNG data URL.
+    fn app_icon(&self, _id: &str) -> Option<String> {
+        None
+    }
+    /// Whether this is `MockWindowProvider`, whose windows get generated thumbnails.
+    fn is_mock(&self) -> bool {
+        false
+    }
+    /// Topmost listed window whose bounds contain the global point. Providers list front to
+    /// back, so the first hit wins.
+    fn window_at_point(&self, x: f64, y: f64) -> Option<String> {
+        self.list(false)
+            .into_iter()
+            .find(|window| window.bounds.is_some_and(|bounds| bounds.contains(x, y)))
+            .map(|window| window.id)
+    }
+    /// Ranked matches of `query` from the provider's incremental search index, by OS id.
+    /// `None` if the provider keeps no index.
+    fn search(&self, _query: &str) -> Option<Vec<search::Hit>> {
+        None
+    }
 }
 
-#[cfg(not(target_os = "macos"))]
-#[derive(Default)]
-struct MockWindowProvider;
+/// Switches listings between the platform's native provider (`native`) and the mock one
+/// (`mock`) without restarting. Requires `Config::enable_provider_switch`.
+#[tauri::command]
+fn set_provider_mode(mode: String, service: State<WindowService>) -> Result<(), CommandError> {
+    if !config().enable_provider_switch {
+        return Err(CommandError::new(
+            "provider_switch_disabled",
+            "Set enable_provider_switch in config.toml to use set_provider_mode",
+        ));
+    }
+    let provider: Arc<dyn WindowProvider> = match mode.to_ascii_lowercase().as_str() {
+        "native" => build_provider(),
+        "mock" => Arc::new(MockWindowProvider::new()),
+        _ => {
+            return Err(CommandError::new("invalid_provider_mode", format!("unknown provider mode {mode:?}"))
+                .with_details(serde_json::json!({ "mode": mode })))
+        }
+    };
+    service.set_provider(provider);
+    log::info!("[rifthold] provider switched to {}", mode);
+    Ok(())
+}
 
-#[cfg(not(target_os = "macos"))]
-impl WindowProvider for MockWindowProvider {
-    fn list(&self, _capture_thumbnails: bool) -> Vec<WindowInfo> {
-        vec![
-            WindowInfo {
-                id: "1".into(),
-                title: "Mock Window — code editor".into(),
-                app_name: "VS Code".into(),
-                is_title_fallback: false,
-                thumbnail: None,
-            },
-            WindowInfo {
+/// The running build's bundle id, detected at startup. Kept out of `Config` so it is never
+/// written to config.toml, where it would outlive the build it was detected from.
+static DETECTED_SELF_BUNDLE_ID: OnceLock<String> = OnceLock::new();
+
+/// Bundle id of the self-filter: `Config::self_bundle_id` if set, else the detected one.
+fn self_bundle_id() -> Option<String> {
+    config()
+        .self_bundle_id
+        .clone()
+        .or_else(|| DETECTED_SELF_BUNDLE_ID.get().cloned())
+}
+
+/// Env var naming a JSON fixture (an array of `WindowInfo`-shaped objects) for the mock provider.
+const MOCK_WINDOWS_ENV: &str = "RIFTHOLD_MOCK_WINDOWS";
+
+/// Env var that, when set to `1`, makes the backend run without any native calls (for CI).
+const HEADLESS_ENV: &str = "RIFTHOLD_HEADLESS";
+
+static HEADLESS: LazyLock<bool> = LazyLock::new(|| std::env::var(HEADLESS_ENV).is_ok_and(|value| value == "1"));
+
+/// Whether `RIFTHOLD_HEADLESS=1` is set. Headless runs use the mock provider on every platform
+/// and skip the macOS-only calls, so these commands become no-ops: `switch_to_english_input`,
+/// `get_window_thumbnail` and `get_window_thumbnail_sizes` (empty), `check_screen_recording_permission`
+/// (always granted) and `get_power_state` (`unknown`); `get_displays` reports the mock screen.
+/// Activation only logs, and no app launch/terminate events are emitted.
+fn headless() -> bool {
+    *HEADLESS
+}
+
+struct MockWindowProvider {
+    windows: Vec<WindowInfo>,
+    /// Fixture windows are served as written (including missing thumbnails); only the
+    /// built-in set gets generated thumbnails.
+    from_fixture: bool,
+}
+
+impl MockWindowProvider {
+    fn new() -> Self {
+        if let Ok(path) = std::env::var(MOCK_WINDOWS_ENV) {
+            match Self::load_fixture(&path) {
+                Ok(windows) => {
+                    log::info!("[rifthold][mock] loaded {} windows from {}", windows.len(), path);
+                    return Self { windows, from_fixture: true };
+                }
+                Err(error) => log::warn!("[rifthold][mock] ignoring fixture {}: {}", path, error),
+            }
+        }
+        Self {
+            windows: Self::builtin_windows(),
+            from_fixture: false,
+        }
+    }
+
+    fn load_fixture(path: &str) -> Result<Vec<WindowInfo>, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut windows: Vec<WindowInfo> = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        for window in &mut windows {
+            if window.stable_key.is_empty() {
+                window.stable_key = stable_window_key(&window.app_name, "window", &window.title);
+            }
+        }
+        Ok(windows)
+    }
+
+    fn builtin_windows() -> Vec<WindowInfo> {
+        vec![
+            WindowInfo {
+                id: "1".into(),
+                title: "Mock Window — code editor".into(),
+                app_name: "VS Code".into(),
+                is_title_fallback: false,
+                stable_key: stable_window_key("VS Code", "window", "Mock Window — code editor"),
+                bounds: Some(WindowBounds { x: 0.0, y: 25.0, width: 960.0, height: 540.0 }),
+                ..Default::default()
+            },
+            WindowInfo {
                 id: "2".into(),
                 title: "Mock Window — product specs".into(),
                 app_name: "Notion".into(),
                 is_title_fallback: false,
-                thumbnail: None,
+                stable_key: stable_window_key("Notion", "window", "Mock Window — product specs"),
+                bounds: Some(WindowBounds { x: 960.0, y: 25.0, width: 960.0, height: 540.0 }),
+                ..Default::default()
             },
             WindowInfo {
                 id: "3".into(),
                 title: "Mock Window — design board".into(),
                 app_name: "Figma".into(),
                 is_title_fallback: false,
-                thumbnail: None,
+                stable_key: stable_window_key("Figma", "window", "Mock Window — design board"),
+                bounds: Some(WindowBounds { x: 0.0, y: 565.0, width: 960.0, height: 540.0 }),
+                ..Default::default()
             },
             WindowInfo {
                 id: "4".into(),
                 title: "Mock Window — browser".into(),
                 app_name: "Arc".into(),
                 is_title_fallback: false,
-                thumbnail: None,
+                stable_key: stable_window_key("Arc", "window", "Mock Window — browser"),
+                bounds: Some(WindowBounds { x: 960.0, y: 565.0, width: 960.0, height: 540.0 }),
+                ..Default::default()
             },
         ]
     }
+}
+
+/// Deterministic gradient thumbnail for a mock window, so the grid renders realistic tiles
+/// off macOS. The palette is derived from an FNV-1a hash of `id`, stable across runs.
+fn mock_thumbnail(id: &str) -> Option<EncodedThumbnail> {
+    let hash = id
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3));
+    let start = [(hash >> 16) as u8, (hash >> 24) as u8, (hash >> 32) as u8];
+    let end = [(hash >> 40) as u8, (hash >> 48) as u8, (hash >> 56) as u8];
+
+    let (width, height) = (800u32, 500u32);
+    let image = image::RgbaImage::from_fn(width, height, |x, y| {
+        let t = (x + y) as f32 / (width + height) as f32;
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t) as u8;
+        // Faux title bar so tiles read as windows rather than swatches.
+        if y < 36 {
+            return image::Rgba([32, 32, 36, 255]);
+        }
+        image::Rgba([mix(start[0], end[0]), mix(start[1], end[1]), mix(start[2], end[2]), 255])
+    });
+
+    let filter = config()
+        .scaler
+        .filter()
+        .unwrap_or(image::imageops::FilterType::Triangle);
+    let thumbnail = encode_rgba_thumbnail(&resize_rgba(&image, thumbnail_width(), filter))?;
+    record_phash(id, thumbnail.phash);
+    Some(thumbnail)
+}
+
+impl WindowProvider for MockWindowProvider {
+    fn list(&self, capture_thumbnails: bool) -> Vec<WindowInfo> {
+        let mut windows = self.windows.clone();
+        if capture_thumbnails {
+            adapt_thumbnail_width(windows.len());
+        }
+        for window in &mut windows {
+            if !capture_thumbnails {
+                window.thumbnail = None;
+            } else if !self.from_fixture {
+                window.thumbnail = mock_thumbnail(&window.id).map(|thumbnail| thumbnail.data_url());
+            }
+        }
+        windows
+    }
+
+    fn list_app(&self, bundle_id: &str, capture_thumbnails: bool) -> Vec<WindowInfo> {
+        // Mock windows have no bundle ids; their stable keys use the app name as the owner.
+        let prefix = format!("{bundle_id}|");
+        self.list(capture_thumbnails)
+            .into_iter()
+            .filter(|window| window.stable_key.starts_with(&prefix))
+            .collect()
+    }
+
+    fn activate(&self, id: &str) -> Result<(), String> {
+        log::info!("activate_window called with id={}", id);
+        Ok(())
+    }
+
+    fn activate_app(&self, id: &str) -> Result<(), String> {
+        log::info!("activate_app_all_windows called with id={}", id);
+        Ok(())
+    }
+
+    fn clear_cache(&self) {
+        // No-op for mock provider
+    }
+
+    fn window_title(&self, id: &str) -> Option<String> {
+        self.windows.iter().find(|window| window.id == id).map(|window| window.title.clone())
+    }
+
+    fn is_mock(&self) -> bool {
+        true
+    }
+
+    fn set_window_frame(&self, id: &str, frame: WindowBounds) -> Result<(), String> {
+        log::info!("set_window_frame called with id={} frame={:?}", id, frame);
+        Ok(())
+    }
+
+    fn launch_app(&self, bundle_id: &str) -> Result<bool, String> {
+        log::info!("launch_app called with bundle_id={}", bundle_id);
+        Ok(false)
+    }
+
+    fn ax_tree(&self, id: &str) -> Result<serde_json::Value, String> {
+        let window = self.windows.iter().find(|window| window.id == id).ok_or("Window not found")?;
+        Ok(serde_json::json!({
+            "role": "AXWindow",
+            "subrole": "AXStandardWindow",
+            "title": window.title,
+            "frame": window.bounds,
+        }))
+    }
+}
+
+#[derive(Clone)]
+struct WindowService {
+    /// Swappable at runtime by `set_provider_mode`; read through `provider()`.
+    provider: Arc<RwLock<Arc<dyn WindowProvider>>>,
+    /// Last OS window id seen for each stable key, so identity-keyed features can re-associate.
+    stable_ids: Arc<Mutex<HashMap<String, String>>>,
+    /// Window ids of the last listing sent to the overlay, grouped and filtered as displayed,
+    /// so ⌘N resolves to the Nth tile. Set by `remember_displayed`.
+    listed_order: Arc<Mutex<Vec<String>>>,
+    /// Two-deep MRU of activated window ids: (current, previous).
+    recent: Arc<Mutex<(Option<String>, Option<String>)>>,
+    /// What the frontend was last sent, for `resend_last_list`.
+    last_emitted: Arc<Mutex<EmittedState>>,
+    /// Frontend-facing ids for `Config::stable_display_ids`.
+    public_ids: Arc<Mutex<PublicIds>>,
+    /// Stable keys flagged by `flag_window`; kept across refreshes, not persisted.
+    flagged: Arc<Mutex<HashSet<String>>>,
+}
+
+#[derive(Default)]
+struct PublicIds {
+    /// Stable key (suffixed `#n` for the nth same-key window of a listing) → assigned number.
+    by_key: HashMap<String, u64>,
+    next: u64,
+    /// Public id → OS window id, and back, for the windows listed so far.
+    to_os: HashMap<String, String>,
+    from_os: HashMap<String, String>,
+}
+
+/// The last `windows:list` payload and the thumbnails emitted for it since.
+#[derive(Default)]
+struct EmittedState {
+    windows: Vec<WindowInfo>,
+    /// Window id → (thumbnail, stale, `THUMBNAIL_VERSION` of its capture).
+    thumbnails: HashMap<String, (EncodedThumbnail, bool, u64)>,
+    /// Window id → hash of the last emitted (thumbnail, stale), to skip re-sending identical frames.
+    thumbnail_hashes: HashMap<String, u64>,
+}
+
+struct ShortcutConfig {
+    current: Mutex<String>,
+    /// Serializes changes to the OS registration (`set_shortcut`, `is_shortcut_available`).
+    registration: Mutex<()>,
+    /// Cleared while the shortcut is suppressed; `current` still holds the chosen combo.
+    enabled: AtomicBool,
+}
+
+/// Counter to cancel stale refresh requests
+static REFRESH_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Monotonic stamp of each thumbnail capture, sent as `version` with `window:thumbnail` so the
+/// frontend can drop a slow older capture that arrives after a newer one of the same window.
+static THUMBNAIL_VERSION: AtomicU64 = AtomicU64::new(0);
+
+/// When the overlay was last asked to open; consumed by the next `windows:list` emission.
+static OPEN_STARTED_AT: Mutex<Option<std::time::Instant>> = Mutex::new(None);
+
+/// Milliseconds from the last shortcut-driven open to its first `windows:list` emission.
+static LAST_OPEN_LATENCY_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Set once `setup` finished; `wait_for_ready` blocks on `READY_CONDVAR` until then.
+static BACKEND_READY: AtomicBool = AtomicBool::new(false);
+static READY_LOCK: Mutex<()> = Mutex::new(());
+static READY_CONDVAR: Condvar = Condvar::new();
+
+/// Marks setup as complete, wakes `wait_for_ready` callers and emits `backend:ready`.
+fn mark_backend_ready<R: Runtime>(app: &AppHandle<R>) {
+    {
+        let _guard = READY_LOCK.lock().unwrap();
+        BACKEND_READY.store(true, Ordering::SeqCst);
+    }
+    READY_CONDVAR.notify_all();
+    let _ = app.emit("backend:ready", ());
+}
+
+/// Resolves once the backend finished `setup`, so the first real call never races it.
+#[tauri::command]
+async fn wait_for_ready() -> Result<(), CommandError> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let mut guard = READY_LOCK.lock().unwrap();
+        while !BACKEND_READY.load(Ordering::SeqCst) {
+            guard = READY_CONDVAR.wait(guard).unwrap();
+        }
+    })
+    .await
+    .map_err(|e| CommandError::new("internal", e.to_string()))
+}
+
+/// Completes an open measurement, if one is pending, and reports it via `overlay:opened`.
+fn record_open_latency<R: Runtime>(app: &AppHandle<R>) {
+    let Some(started_at) = OPEN_STARTED_AT.lock().unwrap().take() else {
+        return;
+    };
+    let latency_ms = started_at.elapsed().as_millis() as u64;
+    LAST_OPEN_LATENCY_MS.store(latency_ms, Ordering::SeqCst);
+    log::debug!("[rifthold] overlay open latency {}ms", latency_ms);
+    let _ = app.emit("overlay:opened", serde_json::json!({ "latencyMs": latency_ms }));
+}
+
+impl WindowService {
+    fn new(provider: Arc<dyn WindowProvider>) -> Self {
+        Self {
+            provider: Arc::new(RwLock::new(provider)),
+            stable_ids: Arc::new(Mutex::new(HashMap::new())),
+            listed_order: Arc::new(Mutex::new(Vec::new())),
+            recent: Arc::new(Mutex::new((None, None))),
+            last_emitted: Arc::new(Mutex::new(EmittedState::default())),
+            public_ids: Arc::new(Mutex::new(PublicIds::default())),
+            flagged: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    fn provider(&self) -> Arc<dyn WindowProvider> {
+        self.provider.read().unwrap().clone()
+    }
+
+    /// Replaces the provider. Ids of the old one mean nothing to the new one, so the MRU, the
+    /// remembered ids and the last emitted list are dropped.
+    fn set_provider(&self, provider: Arc<dyn WindowProvider>) {
+        *self.provider.write().unwrap() = provider;
+        *self.recent.lock().unwrap() = (None, None);
+        self.stable_ids.lock().unwrap().clear();
+        self.listed_order.lock().unwrap().clear();
+        *self.last_emitted.lock().unwrap() = EmittedState::default();
+    }
+
+    fn list(&self, capture_thumbnails: bool) -> Vec<WindowInfo> {
+        let mut windows = self.provider().list(capture_thumbnails);
+        apply_list_options(&mut windows, &config());
+        // A capturing provider adapts the width itself, before it captures; this sets it for
+        // the thumbnails streamed after a listing without them.
+        if !capture_thumbnails {
+            adapt_thumbnail_width(windows.len());
+        }
+        WINDOW_PHASHES
+            .lock()
+            .unwrap()
+            .retain(|id, _| windows.iter().any(|window| &window.id == id));
+        FOCUS_THUMBNAILS
+            .lock()
+            .unwrap()
+            .retain(|id, _| windows.iter().any(|window| window.id == id.to_string()));
+        self.assign_public_ids(&mut windows);
+        self.apply_flags(&mut windows);
+        self.remember_stable_ids(&windows);
+        windows
+    }
+
+    /// Records the order of `windows` as sent to the frontend, after grouping and filtering.
+    fn remember_displayed(&self, windows: &[WindowInfo]) {
+        *self.listed_order.lock().unwrap() = windows.iter().map(|w| w.id.clone()).collect();
+    }
+
+    /// Replaces OS ids (including `parent_id`) with public ones when `stable_display_ids` is on.
+    fn assign_public_ids(&self, windows: &mut [WindowInfo]) {
+        if !config().stable_display_ids {
+            return;
+        }
+        let mut public_ids = self.public_ids.lock().unwrap();
+        let PublicIds { by_key, next, to_os, from_os } = &mut *public_ids;
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+
+        for window in windows.iter_mut() {
+            let occurrence = occurrences.entry(window.stable_key.clone()).or_insert(0);
+            let key = match *occurrence {
+                0 => window.stable_key.clone(),
+                n => format!("{}#{}", window.stable_key, n),
+            };
+            *occurrence += 1;
+
+            let public = by_key
+                .entry(key)
+                .or_insert_with(|| {
+                    *next += 1;
+                    *next
+                })
+                .to_string();
+            let os_id = std::mem::replace(&mut window.id, public.clone());
+            from_os.insert(os_id.clone(), public.clone());
+            to_os.insert(public, os_id);
+        }
+        for window in windows.iter_mut() {
+            if let Some(public) = window.parent_id.as_ref().and_then(|parent| from_os.get(parent)) {
+                window.parent_id = Some(public.clone());
+            }
+        }
+    }
+
+    /// The OS window id behind a frontend-facing id (identity unless `stable_display_ids`).
+    fn os_id(&self, id: &str) -> String {
+        self.public_ids
+            .lock()
+            .unwrap()
+            .to_os
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// The frontend-facing id of an OS window id.
+    fn public_id(&self, os_id: &str) -> String {
+        self.public_ids
+            .lock()
+            .unwrap()
+            .from_os
+            .get(os_id)
+            .cloned()
+            .unwrap_or_else(|| os_id.to_string())
+    }
+
+    /// Id of the `index`th window (0-based) of the last listing, as displayed.
+    fn nth_listed(&self, index: usize) -> Option<String> {
+        self.listed_order.lock().unwrap().get(index).cloned()
+    }
+
+    fn remember_stable_ids(&self, windows: &[WindowInfo]) {
+        let mut stable_ids = self.stable_ids.lock().unwrap();
+        for window in windows {
+            stable_ids.insert(window.stable_key.clone(), window.id.clone());
+        }
+    }
+
+    fn apply_flags(&self, windows: &mut [WindowInfo]) {
+        let flagged = self.flagged.lock().unwrap();
+        for window in windows.iter_mut() {
+            window.flagged = flagged.contains(&window.stable_key);
+        }
+    }
+
+    /// Flags or unflags the window `id` of the last listing; false if `id` is not listed.
+    fn set_flagged(&self, id: &str, flag: bool) -> bool {
+        let stable_key = self
+            .stable_ids
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, listed_id)| listed_id.as_str() == id)
+            .map(|(stable_key, _)| stable_key.clone());
+        let Some(stable_key) = stable_key else {
+            return false;
+        };
+        let mut flagged = self.flagged.lock().unwrap();
+        if flag {
+            flagged.insert(stable_key);
+        } else {
+            flagged.remove(&stable_key);
+        }
+        true
+    }
+
+    /// Resolves a stable key to the most recently listed OS window id.
+    fn resolve_stable_key(&self, stable_key: &str) -> Option<String> {
+        self.stable_ids.lock().unwrap().get(stable_key).cloned()
+    }
+
+    fn list_app(&self, bundle_id: &str, capture_thumbnails: bool) -> Vec<WindowInfo> {
+        let mut windows = self.provider().list_app(bundle_id, capture_thumbnails);
+        apply_list_options(&mut windows, &config());
+        self.assign_public_ids(&mut windows);
+        self.apply_flags(&mut windows);
+        windows
+    }
+
+    fn activate(&self, id: &str) -> Result<(), String> {
+        let os_id = self.os_id(id);
+        self.provider().activate(&os_id)?;
+        if config().focus_time_thumbnails {
+            if let Ok(window_id) = os_id.parse::<i64>() {
+                capture_focus_thumbnail(window_id);
+            }
+        }
+        let mut recent = self.recent.lock().unwrap();
+        if recent.0.as_deref() != Some(id) {
+            recent.1 = recent.0.replace(id.to_string());
+        }
+        Ok(())
+    }
+
+    fn remember_emitted_list(&self, windows: &[WindowInfo]) {
+        let mut emitted = self.last_emitted.lock().unwrap();
+        emitted.thumbnails.retain(|id, _| windows.iter().any(|window| &window.id == id));
+        emitted.thumbnail_hashes.retain(|id, _| windows.iter().any(|window| &window.id == id));
+        emitted.windows = windows.to_vec();
+    }
+
+    /// Re-reads the titles of the last emitted windows and returns `(id, title)` for those that
+    /// changed, updating the emitted list to match. Fallback titles are left alone.
+    fn changed_titles(&self) -> Vec<(String, String)> {
+        let (strip, max_title_length) = {
+            let config = config();
+            (config.strip_app_suffix, config.max_title_length)
+        };
+        // Titles are read through the provider (FFI per window), so not under the lock.
+        let listed: Vec<(String, String, String)> = {
+            let emitted = self.last_emitted.lock().unwrap();
+            emitted
+                .windows
+                .iter()
+                .filter(|window| !window.is_title_fallback)
+                .map(|window| (window.id.clone(), window.app_name.clone(), window.title.clone()))
+                .collect()
+        };
+        let updates: Vec<(String, String, Option<String>)> = listed
+            .into_iter()
+            .filter_map(|(id, app_name, current)| {
+                let raw_title = self.provider().window_title(&self.os_id(&id))?;
+                let title = strip
+                    .then(|| strip_app_suffix(&raw_title, &app_name))
+                    .flatten()
+                    .unwrap_or_else(|| raw_title.clone());
+                let title = max_title_length
+                    .and_then(|max_chars| truncate_title(&title, max_chars))
+                    .unwrap_or(title);
+                (title != current).then(|| {
+                    let raw_title = (title != raw_title).then_some(raw_title);
+                    (id, title, raw_title)
+                })
+            })
+            .collect();
+        if updates.is_empty() {
+            return Vec::new();
+        }
+
+        let mut emitted = self.last_emitted.lock().unwrap();
+        let mut changed = Vec::new();
+        for (id, title, raw_title) in updates {
+            // The list may have been replaced while reading; skip windows no longer in it.
+            if let Some(window) = emitted.windows.iter_mut().find(|window| window.id == id) {
+                window.raw_title = raw_title;
+                window.title = title.clone();
+                changed.push((id, title));
+            }
+        }
+        changed
+    }
+
+    /// Records an emitted thumbnail; false if it is identical to the last one sent for `id`,
+    /// in which case the frontend already shows it. JPEG encoding is deterministic, so equal
+    /// captures give equal bytes and hashing the encoded image stands in for hashing the pixels.
+    fn remember_emitted_thumbnail(&self, id: &str, thumbnail: &EncodedThumbnail, stale: bool, version: u64) -> bool {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        (&thumbnail.bytes, stale).hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut emitted = self.last_emitted.lock().unwrap();
+        if emitted.thumbnail_hashes.insert(id.to_string(), hash) == Some(hash) {
+            return false;
+        }
+        emitted.thumbnails.insert(id.to_string(), (thumbnail.clone(), stale, version));
+        true
+    }
+
+    /// Forgets what was emitted per thumbnail, so the next capture of every window is sent.
+    fn forget_thumbnail_hashes(&self) {
+        self.last_emitted.lock().unwrap().thumbnail_hashes.clear();
+    }
+
+    /// Clears the caches of `kind` (see `invalidate`), leaving the others alone.
+    fn invalidate(&self, kind: CacheKind) {
+        if matches!(kind, CacheKind::Thumbnails | CacheKind::All) {
+            self.provider().clear_thumbnail_cache();
+            FOCUS_THUMBNAILS.lock().unwrap().clear();
+            let mut emitted = self.last_emitted.lock().unwrap();
+            emitted.thumbnails.clear();
+            emitted.thumbnail_hashes.clear();
+        }
+        if matches!(kind, CacheKind::List | CacheKind::All) {
+            self.provider().clear_cache();
+            self.listed_order.lock().unwrap().clear();
+            self.last_emitted.lock().unwrap().windows.clear();
+        }
+        if matches!(kind, CacheKind::Snapshot | CacheKind::All) {
+            self.provider().clear_snapshot();
+        }
+    }
+
+    /// The window activated before the current one, if any.
+    fn previous_window(&self) -> Option<String> {
+        self.recent.lock().unwrap().1.clone()
+    }
+
+    fn activate_app(&self, id: &str) -> Result<(), String> {
+        self.provider().activate_app(&self.os_id(id))
+    }
+
+    fn clear_cache(&self) {
+        self.provider().clear_cache()
+    }
+
+    fn app_icon(&self, id: &str) -> Option<String> {
+        self.provider().app_icon(&self.os_id(id))
+    }
+
+    fn window_bounds(&self, id: &str) -> Option<WindowBounds> {
+        self.provider().window_bounds(&self.os_id(id))
+    }
+
+    fn set_window_frame(&self, id: &str, frame: WindowBounds) -> Result<(), String> {
+        self.provider().set_window_frame(&self.os_id(id), frame)
+    }
+
+    fn ax_tree(&self, id: &str) -> Result<serde_json::Value, String> {
+        self.provider().ax_tree(&self.os_id(id))
+    }
+
+    fn window_at_point(&self, x: f64, y: f64) -> Option<String> {
+        self.provider().window_at_point(x, y).map(|os_id| self.public_id(&os_id))
+    }
+
+    /// Searches the last emitted listing without re-listing, ranked by the provider's index
+    /// when it keeps one and by `filter_windows` otherwise.
+    fn search(&self, query: &str) -> Vec<WindowInfo> {
+        let mut windows = self.last_emitted.lock().unwrap().windows.clone();
+        if windows.is_empty() {
+            windows = self.list(false);
+        }
+        let Some(hits) = self.provider().search(query) else {
+            return filter_windows(windows, Some(query));
+        };
+        let terms: Vec<&str> = query.split_whitespace().collect();
+        let mut by_id: HashMap<String, WindowInfo> =
+            windows.into_iter().map(|window| (window.id.clone(), window)).collect();
+        hits.into_iter()
+            .filter_map(|hit| by_id.remove(&self.public_id(&hit.id)))
+            .map(|mut window| {
+                if !terms.is_empty() {
+                    window.match_ranges = Some(title_match_ranges(&window.title, &terms));
+                }
+                window
+            })
+            .collect()
+    }
+}
+
+fn build_provider() -> Arc<dyn WindowProvider> {
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        return Arc::new(macos::MacWindowProvider::new());
+    }
+
+    #[cfg(target_os = "linux")]
+    if !headless() && std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match wayland::WaylandWindowProvider::connect() {
+            Ok(provider) => return Arc::new(provider),
+            Err(error) => log::warn!("[rifthold][wayland] {}; falling back to the mock provider", error),
+        }
+    }
+
+    Arc::new(MockWindowProvider::new())
+}
+
+#[tauri::command]
+fn list_windows(
+    service: State<WindowService>,
+    refresh_cache: Option<bool>,
+    capture_thumbnails: Option<bool>,
+    query: Option<String>,
+    app: AppHandle,
+) -> Vec<WindowInfo> {
+    let refresh = refresh_cache.unwrap_or(false);
+    let capture = capture_thumbnails.unwrap_or(true);
+
+    log::debug!("[list_windows] refresh_cache={:?} (resolved={}), capture_thumbnails={:?} (resolved={})",
+        refresh_cache, refresh, capture_thumbnails, capture);
+
+    if refresh {
+        service.clear_cache();
+    }
+    let group_mode = config().group_mode;
+    if capture && config().stream_thumbnails_only {
+        let windows = service.list(false);
+        service.remember_emitted_list(&windows);
+        start_thumbnail_stream(&app, &windows);
+        let displayed = filter_windows(group_windows(windows, group_mode), query.as_deref());
+        service.remember_displayed(&displayed);
+        return displayed;
+    }
+    let displayed = filter_windows(group_windows(service.list(capture), group_mode), query.as_deref());
+    service.remember_displayed(&displayed);
+    displayed
+}
+
+/// Applies `GroupMode` to a listing. Listings are cached ungrouped, so the mode can change
+/// without re-listing; a representative keeps its own window id, so activating it raises
+/// that very window.
+fn group_windows(windows: Vec<WindowInfo>, mode: GroupMode) -> Vec<WindowInfo> {
+    if mode == GroupMode::Windows {
+        return windows;
+    }
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for window in &windows {
+        *counts.entry(window.app_name.clone()).or_default() += 1;
+    }
+    let mut seen = HashSet::new();
+    windows
+        .into_iter()
+        .filter(|window| seen.insert(window.app_name.clone()))
+        .map(|mut window| {
+            window.app_window_count = counts.get(&window.app_name).copied();
+            window
+        })
+        .collect()
+}
+
+/// Switches between per-window and per-app listings and re-emits the last listing grouped
+/// accordingly (with its thumbnails), without listing or capturing again.
+#[tauri::command]
+fn set_group_mode(mode: String, service: State<WindowService>, app: AppHandle) -> Result<(), CommandError> {
+    let group_mode: GroupMode = serde_json::from_value(serde_json::Value::String(mode.clone())).map_err(|_| {
+        CommandError::new("invalid_group_mode", format!("unknown group mode {mode:?}"))
+            .with_details(serde_json::json!({ "mode": mode }))
+    })?;
+    update_config(|config| config.group_mode = group_mode)
+        .map_err(|e| CommandError::new("config_write_failed", e))?;
+    emit_last_list(&service, &app)?;
+    Ok(())
+}
+
+/// Keeps the windows matching every whitespace-separated term of `query` (each against the
+/// full title, else the app name), best score first, with `match_ranges` set from the title
+/// hits that are still part of the displayed title.
+fn filter_windows(windows: Vec<WindowInfo>, query: Option<&str>) -> Vec<WindowInfo> {
+    let terms: Vec<&str> = query.unwrap_or_default().split_whitespace().collect();
+    if terms.is_empty() {
+        return windows;
+    }
+
+    let mut scored: Vec<(i64, WindowInfo)> = windows
+        .into_iter()
+        .filter_map(|mut window| {
+            let mut total = 0;
+            let mut title_indices = Vec::new();
+            let full_title = window.raw_title.as_deref().unwrap_or(&window.title);
+            for term in &terms {
+                match search::score(term, full_title, true) {
+                    Some(matched) => {
+                        total += matched.score;
+                        title_indices.extend(matched.indices);
+                    }
+                    None => total += search::score(term, &window.app_name, false)?.score,
+                }
+            }
+            // Shortened titles are a prefix of the full one; hits past it are not shown.
+            let shown = window.title.chars().zip(full_title.chars()).take_while(|(a, b)| a == b).count();
+            title_indices.retain(|&index| index < shown);
+            title_indices.sort_unstable();
+            title_indices.dedup();
+            window.match_ranges = Some(search::match_ranges(&window.title, &title_indices));
+            Some((total, window))
+        })
+        .collect();
+    // Stable, so equally scored windows keep the listing order.
+    scored.sort_by(|a, b| b.0.cmp(&a.0));
+    scored.into_iter().map(|(_, window)| window).collect()
+}
+
+/// Highlight spans of `terms` in `title`, as `filter_windows` computes them.
+fn title_match_ranges(title: &str, terms: &[&str]) -> Vec<(usize, usize)> {
+    let mut indices: Vec<usize> = terms
+        .iter()
+        .filter_map(|term| search::score(term, title, true))
+        .flat_map(|matched| matched.indices)
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    search::match_ranges(title, &indices)
+}
+
+/// Where to put the cursor for a window: the center of its frame, clamped into the display
+/// holding most of it so a window hanging off one screen's edge still lands on that screen.
+fn cursor_target(bounds: &WindowBounds, displays: &[DisplayInfo]) -> (f64, f64) {
+    let (x, y) = (bounds.x + bounds.width / 2.0, bounds.y + bounds.height / 2.0);
+    let Some(display) = display_for_bounds(bounds, displays)
+        .and_then(|id| displays.iter().find(|display| display.id == id))
+    else {
+        return (x, y);
+    };
+    let area = display.bounds;
+    (
+        x.clamp(area.x, area.x + area.width - 1.0),
+        y.clamp(area.y, area.y + area.height - 1.0),
+    )
+}
+
+/// Moves the cursor to the center of window `id`, after it has been raised.
+fn center_cursor_on(service: &WindowService, id: &str) {
+    let Some(bounds) = service.window_bounds(id) else {
+        log::debug!("[rifthold] no bounds for window {}, cursor left in place", id);
+        return;
+    };
+    let (x, y) = cursor_target(&bounds, &displays());
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        macos::warp_cursor(x, y);
+    }
+    log::debug!("[rifthold] cursor moved to ({}, {}) for window {}", x, y, id);
+}
+
+/// Activates window `id` and hides the overlay; with `move_cursor`, also centers the mouse on
+/// the window.
+#[tauri::command]
+fn activate_window(
+    id: String,
+    move_cursor: Option<bool>,
+    service: State<WindowService>,
+    app: tauri::AppHandle,
+) -> Result<(), CommandError> {
+    service
+        .activate(&id)
+        .map_err(|e| CommandError::new("activation_failed", e).with_details(serde_json::json!({ "id": id })))?;
+    if move_cursor.unwrap_or(false) {
+        center_cursor_on(&service, &id);
+    }
+
+    let _ = hide_main_overlay(&app);
+
+    Ok(())
+}
+
+/// Activates the `index`th window (0-based, so the tile labelled ⌘1 is index 0) of the last
+/// listing and returns its id. Lists once if nothing has been listed yet.
+#[tauri::command]
+fn activate_nth_window(
+    index: usize,
+    service: State<WindowService>,
+    app: tauri::AppHandle,
+) -> Result<String, CommandError> {
+    let id = match service.nth_listed(index) {
+        Some(id) => id,
+        None => {
+            service.remember_displayed(&group_windows(service.list(false), config().group_mode));
+            service.nth_listed(index).ok_or_else(|| {
+                CommandError::new("index_out_of_range", format!("no window at index {index}"))
+                    .with_details(serde_json::json!({ "index": index }))
+            })?
+        }
+    };
+
+    service
+        .activate(&id)
+        .map_err(|e| CommandError::new("activation_failed", e).with_details(serde_json::json!({ "id": id })))?;
+
+    let _ = hide_main_overlay(&app);
+
+    Ok(id)
+}
+
+/// Bounces back to the window activated before the current one without opening the overlay.
+/// Returns its id, or `None` after showing the overlay because there is no previous window
+/// (or it no longer exists).
+#[tauri::command]
+fn activate_previous_window(
+    service: State<WindowService>,
+    app: tauri::AppHandle,
+) -> Result<Option<String>, CommandError> {
+    if let Some(id) = service.previous_window() {
+        match service.activate(&id) {
+            Ok(()) => {
+                let _ = hide_main_overlay(&app);
+                return Ok(Some(id));
+            }
+            Err(error) => log::info!("[rifthold] previous window {id} unavailable ({error}); showing overlay"),
+        }
+    }
+
+    show_main_overlay(&app)?;
+    Ok(None)
+}
+
+/// Zero-latency two-window toggle: activates the previous window and nothing else. Unlike
+/// `activate_previous_window` it never shows the overlay and emits no events; with no
+/// previous window it does nothing and returns `None`.
+#[tauri::command]
+fn quick_switch(service: State<WindowService>) -> Result<Option<String>, CommandError> {
+    let Some(id) = service.previous_window() else {
+        log::debug!("[rifthold] quick switch: no previous window");
+        return Ok(None);
+    };
+    service
+        .activate(&id)
+        .map_err(|e| CommandError::new("activation_failed", e).with_details(serde_json::json!({ "id": id })))?;
+    Ok(Some(id))
+}
+
+/// Bumped by every peek; a scheduled restore only runs if no newer peek started.
+static PEEK_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Raises window `id` for `Config::peek_duration_ms`, then hands focus back to the overlay,
+/// which stays open (the overlay was frontmost before the peek, so restoring it restores the
+/// pre-peek state). Does not count as an activation for `activate_previous_window`.
+#[tauri::command]
+fn peek_window(id: String, service: State<WindowService>, app: AppHandle) -> Result<(), CommandError> {
+    let generation = PEEK_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+
+    if let Some(window) = app.get_webview_window("main") {
+        // Let the peeked window show above the overlay until the restore.
+        pin_overlay(&window, false)?;
+    }
+    service
+        .provider()
+        .activate(&service.os_id(&id))
+        .map_err(|e| CommandError::new("activation_failed", e).with_details(serde_json::json!({ "id": id })))?;
+
+    let duration = std::time::Duration::from_millis(config().peek_duration_ms);
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        if PEEK_GENERATION.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if let Some(window) = app.get_webview_window("main") {
+            if window.is_visible().unwrap_or(false) {
+                let _ = focus_overlay(&app, &window);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Brings the whole application owning window `id` forward instead of raising one window.
+#[tauri::command]
+fn activate_app_all_windows(
+    id: String,
+    service: State<WindowService>,
+    app: tauri::AppHandle,
+) -> Result<(), CommandError> {
+    service
+        .activate_app(&id)
+        .map_err(|e| CommandError::new("activation_failed", e).with_details(serde_json::json!({ "id": id })))?;
+
+    let _ = hide_main_overlay(&app);
+
+    Ok(())
+}
+
+/// Persists the overlay's always-on-top behavior and applies it to the live window.
+#[tauri::command]
+fn set_always_on_top(enabled: bool, app: AppHandle) -> Result<(), CommandError> {
+    update_config(|config| config.always_on_top = enabled)
+        .map_err(|e| CommandError::new("config_write_failed", e))?;
+    if let Some(window) = app.get_webview_window("main") {
+        pin_overlay(&window, enabled)?;
+    }
+    Ok(())
+}
+
+/// Scopes the overlay to one app: it is laid over that app's frontmost window instead of the
+/// whole workspace until `detach_overlay`. Emits `overlay:attached` and returns the frame.
+#[tauri::command]
+fn attach_overlay_to_app(bundle_id: String, app: AppHandle) -> Result<WindowBounds, CommandError> {
+    let bounds = front_window_bounds(&app, &bundle_id).ok_or_else(|| {
+        CommandError::new("app_has_no_windows", format!("{bundle_id} has no open windows"))
+            .with_details(serde_json::json!({ "bundleId": bundle_id }))
+    })?;
+    *ATTACHED_APP.lock().unwrap() = Some(bundle_id.clone());
+    if let Some(window) = app.get_webview_window("main") {
+        if window.is_visible()? {
+            fit_overlay(&app, &window)?;
+        }
+    }
+    app.emit("overlay:attached", serde_json::json!({ "bundleId": bundle_id, "bounds": bounds }))?;
+    Ok(bounds)
+}
+
+/// Returns the overlay to covering the whole workspace.
+#[tauri::command]
+fn detach_overlay(app: AppHandle) -> Result<(), CommandError> {
+    if ATTACHED_APP.lock().unwrap().take().is_none() {
+        return Ok(());
+    }
+    if let Some(window) = app.get_webview_window("main") {
+        if window.is_visible()? {
+            fit_overlay(&app, &window)?;
+        }
+    }
+    app.emit("overlay:detached", ())?;
+    Ok(())
+}
+
+/// JSON dump of what the accessibility API sees for window `id`, to attach to bug reports
+/// about title-based activation. Requires `Config::enable_ax_dump`.
+#[tauri::command]
+fn dump_ax_tree(id: String, service: State<WindowService>) -> Result<String, CommandError> {
+    if !config().enable_ax_dump {
+        return Err(CommandError::new("ax_dump_disabled", "Set enable_ax_dump in config.toml to use dump_ax_tree"));
+    }
+    let tree = service.ax_tree(&id).map_err(|e| {
+        CommandError::new("ax_dump_failed", e).with_details(serde_json::json!({ "windowId": id }))
+    })?;
+    Ok(serde_json::to_string_pretty(&tree).unwrap_or_default())
+}
+
+/// Every Space of every display. Empty when headless or the private Spaces API is missing.
+#[tauri::command]
+fn list_spaces() -> Vec<spaces::SpaceInfo> {
+    if headless() {
+        return Vec::new();
+    }
+    spaces::list_spaces()
+}
+
+/// Switches a display to `space_id` (an id from `list_spaces`). Uses a private API.
+#[tauri::command]
+fn switch_to_space(space_id: i64) -> Result<(), CommandError> {
+    if headless() || !spaces::available() {
+        return Err(CommandError::new("spaces_unavailable", "Spaces API is not available"));
+    }
+    spaces::switch_to_space(space_id).map_err(|e| {
+        CommandError::new("space_switch_failed", e).with_details(serde_json::json!({ "spaceId": space_id }))
+    })
+}
+
+/// Ranked search over the current windows, served from the backend's search index.
+#[tauri::command]
+fn search_windows(query: String, service: State<WindowService>) -> Vec<WindowInfo> {
+    group_windows(service.search(&query), config().group_mode)
+}
+
+#[tauri::command]
+fn get_app_priority() -> Vec<String> {
+    config().app_priority.clone()
+}
+
+/// Persists the ordered list of app names whose windows are listed first.
+#[tauri::command]
+fn set_app_priority(apps: Vec<String>) -> Result<(), CommandError> {
+    update_config(|config| config.app_priority = apps)
+        .map_err(|e| CommandError::new("config_write_failed", e))?;
+    Ok(())
+}
+
+/// Dismisses the overlay without activating anything, regardless of its current state.
+#[tauri::command]
+fn hide_overlay(app: AppHandle) -> Result<(), CommandError> {
+    hide_main_overlay(&app)?;
+    Ok(())
+}
+
+/// Whether the overlay is showing right now, for a frontend reconciling its own idea of that
+/// (after a reload, or a hide the backend did on its own).
+#[tauri::command]
+fn is_overlay_visible(app: AppHandle) -> Result<bool, CommandError> {
+    match app.get_webview_window("main") {
+        Some(window) => Ok(window.is_visible()?),
+        None => Ok(false),
+    }
+}
+
+#[tauri::command]
+fn list_app_windows(
+    bundle_id: String,
+    service: State<WindowService>,
+    capture_thumbnails: Option<bool>,
+    app: AppHandle,
+) -> Vec<WindowInfo> {
+    let capture = capture_thumbnails.unwrap_or(true);
+    if capture && config().stream_thumbnails_only {
+        let windows = service.list_app(&bundle_id, false);
+        start_thumbnail_stream(&app, &windows);
+        return windows;
+    }
+    service.list_app(&bundle_id, capture)
+}
+
+/// `Config::stream_thumbnails_only` for the sync list commands: starts a new refresh
+/// generation and streams the thumbnails of the already returned `windows` in the background.
+fn start_thumbnail_stream(app: &AppHandle, windows: &[WindowInfo]) {
+    let current_gen = REFRESH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    tauri::async_runtime::spawn(stream_thumbnails(app.clone(), windows.to_vec(), current_gen));
+}
+
+/// Serializes the current window list as pretty JSON for scripting, optionally also writing it
+/// to `path`.
+#[tauri::command]
+fn export_windows_json(path: Option<String>, service: State<WindowService>) -> Result<String, CommandError> {
+    let windows = service.list(false);
+    let exported: Vec<ExportedWindow> = windows
+        .iter()
+        .map(|window| ExportedWindow {
+            id: &window.id,
+            title: window.raw_title.as_deref().unwrap_or(&window.title),
+            app_name: &window.app_name,
+            bounds: window.bounds,
+        })
+        .collect();
+
+    let json = serde_json::to_string_pretty(&exported)
+        .map_err(|e| CommandError::new("serialize_failed", e.to_string()))?;
+
+    if let Some(path) = path {
+        fs::write(&path, &json).map_err(|e| {
+            CommandError::new("export_write_failed", e.to_string()).with_details(serde_json::json!({ "path": path }))
+        })?;
+    }
+
+    Ok(json)
+}
+
+/// What `activate_or_launch` did.
+#[derive(Serialize, Debug)]
+#[serde(tag = "outcome", rename_all = "camelCase")]
+enum LaunchOutcome {
+    /// An open window of the app was activated.
+    #[serde(rename_all = "camelCase")]
+    ActivatedWindow { window_id: String },
+    /// The app was running without listed windows and was brought forward.
+    ActivatedApp,
+    /// The app was not running and has been launched.
+    Launched,
+}
+
+/// Brings up the app `bundle_id` whether or not it is running: its first listed window if it
+/// has one, else the app itself via `open -b` (which launches it when needed).
+#[tauri::command]
+fn activate_or_launch(bundle_id: String, service: State<WindowService>, app: AppHandle) -> Result<LaunchOutcome, CommandError> {
+    let details = || serde_json::json!({ "bundleId": bundle_id });
+    if let Some(window) = service.list_app(&bundle_id, false).into_iter().next() {
+        service
+            .activate(&window.id)
+            .map_err(|e| CommandError::new("activation_failed", e).with_details(details()))?;
+        let _ = hide_main_overlay(&app);
+        return Ok(LaunchOutcome::ActivatedWindow { window_id: window.id });
+    }
+
+    let was_running = service
+        .provider()
+        .launch_app(&bundle_id)
+        .map_err(|e| CommandError::new("launch_failed", e).with_details(details()))?;
+    let _ = hide_main_overlay(&app);
+    log::info!("[rifthold] {} {}", if was_running { "activated" } else { "launched" }, bundle_id);
+    Ok(if was_running { LaunchOutcome::ActivatedApp } else { LaunchOutcome::Launched })
+}
+
+/// Saved window arrangements: layout name → stable key → frame.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SavedLayouts {
+    layouts: HashMap<String, HashMap<String, WindowBounds>>,
+}
+
+fn last_list_path() -> PathBuf {
+    config_path().with_file_name("last_list.json")
+}
+
+/// Prefix given to the ids of persisted windows: window ids are reused across restarts, so a
+/// persisted id must never reach activation as the id of whatever window has it now.
+const PERSISTED_ID_PREFIX: &str = "persisted:";
+
+/// The previous session's list, emitted once by the first refresh.
+static PERSISTED_LIST: Mutex<Option<Vec<WindowInfo>>> = Mutex::new(None);
+
+/// Writes the last emitted listing, without thumbnails, for the next session.
+fn save_last_list(service: &WindowService) {
+    let windows: Vec<WindowInfo> = service
+        .last_emitted
+        .lock()
+        .unwrap()
+        .windows
+        .iter()
+        .map(|window| WindowInfo {
+            thumbnail: None,
+            corner_glyph: None,
+            match_ranges: None,
+            ..window.clone()
+        })
+        .collect();
+    // Nothing was listed this session; keep the previous file.
+    if windows.is_empty() {
+        return;
+    }
+    let path = last_list_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let result = serde_json::to_string(&windows)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => log::info!("[rifthold] persisted {} windows to {}", windows.len(), path.display()),
+        Err(error) => log::warn!("[rifthold] failed to persist the window list: {}", error),
+    }
+}
+
+/// Loads the list saved by `save_last_list`, marked stale and with prefixed ids.
+fn load_persisted_list() {
+    let path = last_list_path();
+    let Ok(content) = fs::read_to_string(&path) else {
+        return;
+    };
+    let windows: Vec<WindowInfo> = match serde_json::from_str(&content) {
+        Ok(windows) => windows,
+        Err(error) => {
+            log::warn!("[rifthold] ignoring unreadable {}: {}", path.display(), error);
+            return;
+        }
+    };
+    let windows: Vec<WindowInfo> = windows
+        .into_iter()
+        .map(|mut window| {
+            window.id = format!("{PERSISTED_ID_PREFIX}{}", window.id);
+            window.parent_id = None;
+            window.stale = true;
+            window
+        })
+        .collect();
+    log::info!("[rifthold] loaded {} persisted windows", windows.len());
+    *PERSISTED_LIST.lock().unwrap() = Some(windows);
+}
+
+fn layouts_path() -> PathBuf {
+    config_path().with_file_name("layouts.toml")
+}
+
+fn load_layouts() -> Result<SavedLayouts, CommandError> {
+    match fs::read_to_string(layouts_path()) {
+        Ok(content) => toml::from_str(&content).map_err(|e| CommandError::new("layouts_corrupt", e.to_string())),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(SavedLayouts::default()),
+        Err(error) => Err(CommandError::new("layouts_read_failed", error.to_string())),
+    }
+}
+
+/// Records the frame of every listed window under `name`, keyed by stable key, replacing any
+/// layout of that name. Returns how many windows were saved.
+#[tauri::command]
+fn save_layout(name: String, service: State<WindowService>) -> Result<usize, CommandError> {
+    let frames: HashMap<String, WindowBounds> = service
+        .list(false)
+        .into_iter()
+        .filter_map(|window| Some((window.stable_key, window.bounds?)))
+        .collect();
+    let saved = frames.len();
+
+    let mut layouts = load_layouts()?;
+    layouts.layouts.insert(name.clone(), frames);
+    let path = layouts_path();
+    fs::create_dir_all(path.parent().unwrap()).map_err(|e| CommandError::new("layouts_write_failed", e.to_string()))?;
+    let content = toml::to_string(&layouts).map_err(|e| CommandError::new("serialize_failed", e.to_string()))?;
+    fs::write(&path, content).map_err(|e| CommandError::new("layouts_write_failed", e.to_string()))?;
+    log::info!("[rifthold] saved layout {:?} with {} windows", name, saved);
+    Ok(saved)
+}
+
+/// Stable keys of a restored layout, by outcome.
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct LayoutRestoreReport {
+    restored: Vec<String>,
+    /// Saved windows that are not open now.
+    skipped: Vec<String>,
+    /// Open windows whose app refused the new frame.
+    failed: Vec<String>,
+}
+
+/// Moves and resizes the open windows of layout `name` back to their saved frames.
+#[tauri::command]
+fn restore_layout(name: String, service: State<WindowService>) -> Result<LayoutRestoreReport, CommandError> {
+    let layouts = load_layouts()?;
+    let frames = layouts.layouts.get(&name).ok_or_else(|| {
+        CommandError::new("layout_not_found", format!("no layout named {name:?}"))
+            .with_details(serde_json::json!({ "name": name }))
+    })?;
+
+    let windows = service.list(false);
+    let mut report = LayoutRestoreReport::default();
+    for (stable_key, frame) in frames {
+        let Some(window) = windows.iter().find(|window| &window.stable_key == stable_key) else {
+            report.skipped.push(stable_key.clone());
+            continue;
+        };
+        match service.set_window_frame(&window.id, *frame) {
+            Ok(()) => report.restored.push(stable_key.clone()),
+            Err(error) => {
+                log::warn!("[rifthold] could not restore {:?}: {}", stable_key, error);
+                report.failed.push(stable_key.clone());
+            }
+        }
+    }
+    log::info!(
+        "[rifthold] restored layout {:?}: restored={} skipped={} failed={}",
+        name,
+        report.restored.len(),
+        report.skipped.len(),
+        report.failed.len()
+    );
+    Ok(report)
+}
+
+/// Names of the saved layouts, sorted.
+#[tauri::command]
+fn list_layouts() -> Result<Vec<String>, CommandError> {
+    let mut names: Vec<String> = load_layouts()?.layouts.into_keys().collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Marks window `id` as needing attention (or clears it) for external integrations, and
+/// tells the frontend via `window:flagged`. The flag follows the window's stable key.
+#[tauri::command]
+fn flag_window(id: String, flag: bool, service: State<WindowService>, app: AppHandle) -> Result<(), CommandError> {
+    if !service.set_flagged(&id, flag) {
+        return Err(CommandError::new("window_not_found", format!("window id {id} is not listed"))
+            .with_details(serde_json::json!({ "id": id })));
+    }
+    let _ = app.emit("window:flagged", serde_json::json!({ "id": id, "flagged": flag }));
+    Ok(())
+}
+
+/// Maps a `stable_key` back to the OS window id from the most recent listing.
+#[tauri::command]
+fn resolve_window_key(stable_key: String, service: State<WindowService>) -> Option<String> {
+    service.resolve_stable_key(&stable_key)
+}
+
+#[tauri::command]
+fn get_window_thumbnail(window_id: String, crop: Option<CropRect>, service: State<WindowService>) -> Option<String> {
+    let window_id = service.os_id(&window_id);
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        let id = window_id.parse::<i64>().ok()?;
+        return macos::capture_window_thumbnail_cropped(id, 500, crop);
+    }
+
+    let _ = (window_id, crop);
+    None
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ThumbnailCapture {
+    thumbnail: String,
+    /// An earlier capture stands in for one that is not possible right now.
+    thumbnail_stale: bool,
+}
+
+/// Captures window `window_id` the way a listing does: windows that cannot be captured right
+/// now (minimized, on another Space, on a sleeping display) get their last good capture,
+/// marked stale.
+#[tauri::command]
+fn get_window_thumbnail_or_cached(window_id: String, service: State<WindowService>) -> Result<ThumbnailCapture, CommandError> {
+    let id = service
+        .os_id(&window_id)
+        .parse::<i64>()
+        .map_err(|_| CommandError::new("invalid_window_id", format!("window id {window_id} is not numeric")))?;
+    let (thumbnail, thumbnail_stale) = capture_thumbnail_now(id, thumbnail_width()).map_err(|error| {
+        CommandError::new("capture_failed", error.to_string())
+            .with_details(serde_json::json!({ "id": window_id, "reason": error }))
+    })?;
+    Ok(ThumbnailCapture {
+        thumbnail: thumbnail.data_url(),
+        thumbnail_stale,
+    })
+}
+
+/// Captures any window by its raw CGWindowID, including ones the listing filters out (system
+/// windows, other layers). Unlike `get_window_thumbnail`, the id is neither translated nor
+/// looked up among listed windows. Elsewhere only the mock provider has anything to capture.
+#[tauri::command]
+fn capture_by_raw_id(cg_window_id: i64, service: State<WindowService>) -> Option<String> {
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        return macos::capture_window_thumbnail(cg_window_id, THUMBNAIL_WIDTH);
+    }
+
+    if !service.provider().is_mock() {
+        return None;
+    }
+    mock_thumbnail(&cg_window_id.to_string()).map(|thumbnail| thumbnail.data_url())
+}
+
+/// Width of `capture_overlay_self` screenshots, large enough to read the overlay's text.
+const OVERLAY_SCREENSHOT_WIDTH: u32 = 1600;
+
+/// Screenshot of the overlay itself as a data URL, for bug reports: the overlay stays on top
+/// and hides on focus loss, so users cannot easily take one themselves.
+#[tauri::command]
+fn capture_overlay_self(app: AppHandle) -> Option<String> {
+    let window = app.get_webview_window("main")?;
+
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        let window_id = macos::window_number(window.ns_window().ok()? as _);
+        return macos::capture_window_thumbnail(window_id, OVERLAY_SCREENSHOT_WIDTH);
+    }
+
+    let _ = window;
+    None
+}
+
+/// "Always show something": captures `window_id` within `timeout_ms`, and on failure or
+/// timeout returns the owning app's icon instead when `fallback_app_icon` is set.
+#[tauri::command]
+async fn capture_with_fallback(
+    window_id: String,
+    fallback_app_icon: bool,
+    timeout_ms: u64,
+    service: State<'_, WindowService>,
+) -> Result<Option<String>, CommandError> {
+    let service = service.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let thumbnail = service.os_id(&window_id).parse::<i64>().ok().and_then(|id| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            // A capture that outlives the timeout finishes in the background and is dropped.
+            std::thread::spawn(move || {
+                let _ = sender.send(capture_thumbnail(id, thumbnail_width()).ok());
+            });
+            match receiver.recv_timeout(std::time::Duration::from_millis(timeout_ms)) {
+                Ok(captured) => captured.map(|(thumbnail, _stale)| thumbnail.data_url()),
+                Err(_) => {
+                    log::debug!("[thumbnail] window_id={} capture timed out after {}ms", id, timeout_ms);
+                    None
+                }
+            }
+        });
+        thumbnail.or_else(|| fallback_app_icon.then(|| service.app_icon(&window_id)).flatten())
+    })
+    .await
+    .map_err(|e| CommandError::new("internal", e.to_string()))
+}
+
+#[tauri::command]
+fn get_window_thumbnail_sizes(
+    window_id: String,
+    widths: Vec<u32>,
+    service: State<WindowService>,
+) -> HashMap<u32, String> {
+    let window_id = service.os_id(&window_id);
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        let Ok(id) = window_id.parse::<i64>() else {
+            return HashMap::new();
+        };
+        return macos::capture_window_thumbnail_sizes(id, &widths);
+    }
+
+    let _ = (window_id, widths);
+    HashMap::new()
+}
+
+/// Latency of the most recent overlay open in milliseconds (0 until one has been measured).
+#[tauri::command]
+fn get_last_open_latency() -> u64 {
+    LAST_OPEN_LATENCY_MS.load(Ordering::SeqCst)
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+enum PowerState {
+    Ac,
+    Battery,
+    Unknown,
+}
+
+fn power_state() -> PowerState {
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        return macos::power_state();
+    }
+
+    PowerState::Unknown
+}
+
+/// Set while thumbnail capture is throttled by `Config::thumbnail_cpu_budget`.
+static BATTERY_SAVING: AtomicBool = AtomicBool::new(false);
+
+/// Re-checks the power source and returns how many thumbnails may be captured concurrently.
+/// Logs whenever the battery adaptation switches on or off.
+fn thumbnail_concurrency() -> usize {
+    let budget = config().thumbnail_cpu_budget;
+    let saving = budget.is_some() && power_state() == PowerState::Battery;
+    if BATTERY_SAVING.swap(saving, Ordering::SeqCst) != saving {
+        match budget {
+            Some(budget) if saving => log::info!(
+                "[thumbnail] on battery: capturing at most {budget} thumbnails at once with lower scaling quality"
+            ),
+            _ => log::info!("[thumbnail] battery adaptation off; full-quality captures resumed"),
+        }
+    }
+    match budget {
+        Some(budget) if saving => budget.max(1),
+        _ => usize::MAX,
+    }
+}
+
+/// Capture pool of the current `thumbnail_concurrency()` width and that width; kept across
+/// refreshes so battery mode does not spawn and tear down threads on every listing.
+static THUMBNAIL_POOL: Mutex<Option<(usize, Arc<rayon::ThreadPool>)>> = Mutex::new(None);
+
+/// Pool no wider than `thumbnail_concurrency()` for capture passes, rebuilt only when that
+/// width changes. `None` while captures are not capped (or the pool cannot be built).
+fn thumbnail_pool() -> Option<Arc<rayon::ThreadPool>> {
+    let concurrency = thumbnail_concurrency();
+    if concurrency == usize::MAX {
+        return None;
+    }
+    let mut cached = THUMBNAIL_POOL.lock().unwrap();
+    if let Some((width, pool)) = cached.as_ref() {
+        if *width == concurrency {
+            return Some(Arc::clone(pool));
+        }
+    }
+    let pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(concurrency)
+            .thread_name(|index| format!("thumbnail-{index}"))
+            .build()
+            .ok()?,
+    );
+    *cached = Some((concurrency, Arc::clone(&pool)));
+    Some(pool)
+}
+
+/// Ids of the other listed windows, most similar thumbnail first (Hamming distance of dHashes).
+/// Windows without a hash yet are left out.
+#[tauri::command]
+fn find_similar_windows(target_id: String, service: State<WindowService>) -> Result<Vec<String>, CommandError> {
+    // Ids are resolved and the hashes copied out first: `WINDOW_PHASHES` is taken under a
+    // `config()` guard by `apply_list_options`, so no other lock is taken while holding it.
+    let listed = service.listed_order.lock().unwrap().clone();
+    let target_os_id = service.os_id(&target_id);
+    let candidates: Vec<(String, String)> = listed
+        .into_iter()
+        .filter(|id| id != &target_id)
+        .map(|id| (service.os_id(&id), id))
+        .collect();
+    let (target, hashes) = {
+        let phashes = WINDOW_PHASHES.lock().unwrap();
+        let hashes: Vec<(u64, String)> = candidates
+            .into_iter()
+            .filter_map(|(os_id, id)| phashes.get(&os_id).map(|&hash| (hash, id)))
+            .collect();
+        (phashes.get(&target_os_id).copied(), hashes)
+    };
+    let target = target.ok_or_else(|| {
+        CommandError::new("phash_unavailable", format!("no thumbnail hash for window {target_id}"))
+            .with_details(serde_json::json!({ "computePhash": config().compute_phash }))
+    })?;
+
+    let mut ranked: Vec<(u32, String)> = hashes
+        .into_iter()
+        .map(|(hash, id)| ((hash ^ target).count_ones(), id))
+        .collect();
+    ranked.sort_by_key(|(distance, _)| *distance);
+    Ok(ranked.into_iter().map(|(_, id)| id).collect())
+}
+
+/// Id of the topmost window under the global point (screen points, top-left origin). Our own
+/// overlay is never listed, so it never matches.
+#[tauri::command]
+fn window_at_point(x: f64, y: f64, service: State<WindowService>) -> Option<String> {
+    service.window_at_point(x, y)
+}
+
+#[tauri::command]
+fn get_displays() -> Vec<DisplayInfo> {
+    displays()
+}
+
+/// Pins the overlay to display `display_id`, or with `None` lets it follow the current
+/// monitor again.
+#[tauri::command]
+fn set_overlay_display(display_id: Option<u32>) -> Result<(), CommandError> {
+    if let Some(display_id) = display_id {
+        if !displays().iter().any(|display| display.id == display_id) {
+            return Err(CommandError::new("display_not_found", format!("display {display_id} is not attached"))
+                .with_details(serde_json::json!({ "displayId": display_id })));
+        }
+    }
+    update_config(|config| config.overlay_display = display_id)
+        .map_err(|e| CommandError::new("config_write_failed", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_overlay_display() -> Option<u32> {
+    config().overlay_display
+}
+
+#[tauri::command]
+fn get_power_state() -> PowerState {
+    power_state()
+}
+
+/// Whether one capture API is built into this binary and can capture right now.
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct BackendStatus {
+    compiled: bool,
+    usable: bool,
+    /// Why it is not usable, when it is compiled in but isn't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CaptureBackends {
+    core_graphics: BackendStatus,
+    screen_capture_kit: BackendStatus,
+    /// The configured `capture_backend`.
+    configured: CaptureBackend,
+    /// What `Auto` resolves to on this machine.
+    auto_resolves_to: CaptureBackend,
+}
+
+/// Reports which thumbnail capture APIs exist and work here, to explain missing thumbnails.
+#[tauri::command]
+fn capture_backends() -> CaptureBackends {
+    let configured = config().capture_backend;
+
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        let permission = macos::has_screen_recording_permission();
+        let status = |available: bool, missing: &str| BackendStatus {
+            compiled: true,
+            usable: available && permission,
+            reason: if !available {
+                Some(missing.to_string())
+            } else if !permission {
+                Some("screen recording permission not granted".to_string())
+            } else {
+                None
+            },
+        };
+        let screencapturekit = macos::screencapturekit_available();
+        return CaptureBackends {
+            core_graphics: status(true, ""),
+            screen_capture_kit: status(screencapturekit, "requires macOS 14 or newer"),
+            configured,
+            auto_resolves_to: if screencapturekit { CaptureBackend::ScreenCaptureKit } else { CaptureBackend::CoreGraphics },
+        };
+    }
+
+    CaptureBackends {
+        core_graphics: BackendStatus::default(),
+        screen_capture_kit: BackendStatus::default(),
+        configured,
+        auto_resolves_to: CaptureBackend::CoreGraphics,
+    }
+}
+
+#[tauri::command]
+fn check_screen_recording_permission() -> bool {
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        return macos::has_screen_recording_permission();
+    }
+
+    true
+}
+
+/// JSON bundle for bug reports: app info, effective config, permissions, capture backends,
+/// the last emitted listing with its metrics, and recent log lines. Window titles and document
+/// paths are left out, and scrubbed from the log lines, unless `include_titles` is set.
+#[tauri::command]
+fn collect_diagnostics(include_titles: Option<bool>, service: State<WindowService>, app: AppHandle) -> String {
+    let include_titles = include_titles.unwrap_or(false);
+    let windows = service.last_emitted.lock().unwrap().windows.clone();
+
+    let listed: Vec<serde_json::Value> = windows
+        .iter()
+        .map(|window| {
+            let mut entry = serde_json::json!({
+                "id": window.id,
+                "appName": window.app_name,
+                "isTitleFallback": window.is_title_fallback,
+                "hasThumbnail": window.thumbnail.is_some(),
+                "stale": window.stale,
+            });
+            if include_titles {
+                entry["title"] = serde_json::json!(window.title);
+                entry["documentPath"] = serde_json::json!(window.document_path);
+            }
+            entry
+        })
+        .collect();
+
+    let mut log_lines = logging::recent_lines();
+    if !include_titles {
+        let sensitive: Vec<&str> = windows
+            .iter()
+            .filter(|window| !window.is_title_fallback)
+            .flat_map(|window| [Some(&window.title), window.raw_title.as_ref(), window.document_path.as_ref()])
+            .flatten()
+            .map(String::as_str)
+            .filter(|text| !text.is_empty())
+            .collect();
+        for line in &mut log_lines {
+            for text in &sensitive {
+                if line.contains(text) {
+                    *line = line.replace(text, "<redacted>");
+                }
+            }
+        }
+    }
+
+    // Each of these reads the config itself, so none may run while a config guard is held.
+    let effective_config = serde_json::to_value(&*config()).unwrap_or_default();
+    let backends = capture_backends();
+    let screen_recording = check_screen_recording_permission();
+    let power = power_state();
+    let last_metrics = LAST_LIST_METRICS.lock().unwrap().clone();
+
+    let package = app.package_info();
+    let diagnostics = serde_json::json!({
+        "app": {
+            "name": package.name,
+            "version": package.version.to_string(),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+            "headless": headless(),
+            "logLevel": logging::level().to_string(),
+        },
+        "config": effective_config,
+        "permissions": { "screenRecording": screen_recording },
+        "captureBackends": backends,
+        "powerState": power,
+        "windowCount": windows.len(),
+        "windows": listed,
+        "lastListMetrics": last_metrics,
+        "lastOpenLatencyMs": LAST_OPEN_LATENCY_MS.load(Ordering::SeqCst),
+        "recentLogs": log_lines,
+    });
+    serde_json::to_string_pretty(&diagnostics).unwrap_or_default()
+}
+
+#[tauri::command]
+fn log_debug(msg: String) {
+    log::info!("{}", msg);
+}
+
+#[tauri::command]
+fn set_log_level(level: String) -> Result<(), CommandError> {
+    let filter = logging::parse_level(&level)
+        .ok_or_else(|| CommandError::new("invalid_log_level", format!("unknown log level '{level}'")))?;
+    logging::set_level(filter);
+    log::info!("[rifthold] log level set to {}", filter);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_log_level() -> String {
+    logging::level().to_string().to_lowercase()
+}
+
+#[tauri::command]
+fn switch_to_english_input() {
+    #[cfg(target_os = "macos")]
+    if !headless() {
+        macos::switch_to_english_input();
+    }
+}
+
+#[tauri::command]
+fn get_shortcut(config: State<ShortcutConfig>) -> String {
+    config.current.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn set_shortcut(app: AppHandle, config: State<ShortcutConfig>, shortcut: String) -> Result<(), CommandError> {
+    let parsed: Shortcut = shortcut
+        .parse()
+        .map_err(|e| CommandError::new("invalid_shortcut", format!("{:?}", e)))?;
+    let _registration = config.registration.lock().unwrap();
+
+    // While suppressed, only the stored combo changes; enabling registers it.
+    if config.enabled.load(Ordering::SeqCst) {
+        let previous = config.current.lock().unwrap().parse::<Shortcut>();
+        if let Ok(previous) = previous {
+            app.global_shortcut().unregister(previous).map_err(|e| CommandError::new("shortcut_unregister_failed", e.to_string()))?;
+        }
+
+        register_action_shortcut(&app, parsed, ShortcutAction::Toggle)
+            .map_err(|e| CommandError::new("shortcut_register_failed", e.to_string()))?;
+    }
 
-    fn activate(&self, id: &str) -> Result<(), String> {
-        println!("activate_window called with id={}", id);
-        Ok(())
+    *config.current.lock().unwrap() = shortcut.clone();
+    update_config(|config| {
+        if config.shortcuts.contains_key("toggle") {
+            config.shortcuts.insert("toggle".into(), shortcut.clone());
+        }
+        config.shortcut = shortcut;
+    })
+    .map_err(|e| CommandError::new("config_write_failed", e))?;
+    Ok(())
+}
+
+/// Temporarily suppresses (or restores) the overlay shortcut without forgetting or persisting
+/// anything; the stored combo is registered again when re-enabled.
+#[tauri::command]
+fn set_shortcut_enabled(app: AppHandle, config: State<ShortcutConfig>, enabled: bool) -> Result<(), CommandError> {
+    let _registration = config.registration.lock().unwrap();
+    if config.enabled.load(Ordering::SeqCst) == enabled {
+        return Ok(());
     }
 
-    fn clear_cache(&self) {
-        // No-op for mock provider
+    // Only the overlay toggle; the other `Config::shortcuts` actions stay registered.
+    let shortcut = config.current.lock().unwrap().clone();
+    let parsed: Shortcut = shortcut
+        .parse()
+        .map_err(|e| CommandError::new("invalid_shortcut", format!("{:?}", e)))?;
+    if enabled {
+        register_action_shortcut(&app, parsed, ShortcutAction::Toggle)
+            .map_err(|e| CommandError::new("shortcut_register_failed", e.to_string()))?;
+    } else {
+        app.global_shortcut()
+            .unregister(parsed)
+            .map_err(|e| CommandError::new("shortcut_unregister_failed", e.to_string()))?;
     }
+
+    config.enabled.store(enabled, Ordering::SeqCst);
+    log::info!("[rifthold] overlay shortcut {}", if enabled { "enabled" } else { "suppressed" });
+    Ok(())
 }
 
-struct WindowService {
-    provider: Arc<dyn WindowProvider>,
+/// Unregisters and registers every configured shortcut again, for when the OS silently
+/// dropped them. Runs automatically on macOS screen unlock. A suppressed overlay toggle stays
+/// unregistered.
+#[tauri::command]
+fn reregister_shortcuts(app: AppHandle) -> Result<(), CommandError> {
+    reregister_all_shortcuts(&app)
 }
 
-struct ShortcutConfig {
-    current: Mutex<String>,
+fn reregister_all_shortcuts(app: &AppHandle) -> Result<(), CommandError> {
+    let shortcuts = app.state::<ShortcutConfig>();
+    let _registration = shortcuts.registration.lock().unwrap();
+    let toggle: Shortcut = shortcuts
+        .current
+        .lock()
+        .unwrap()
+        .parse()
+        .map_err(|e| CommandError::new("invalid_shortcut", format!("{:?}", e)))?;
+    app.global_shortcut()
+        .unregister_all()
+        .map_err(|e| CommandError::new("shortcut_unregister_failed", e.to_string()))?;
+    register_configured_shortcuts(app, shortcuts.enabled.load(Ordering::SeqCst).then_some(toggle))
+        .map_err(|e| CommandError::new("shortcut_register_failed", e.to_string()))?;
+    Ok(())
 }
 
-/// Counter to cancel stale refresh requests
-static REFRESH_GENERATION: AtomicU64 = AtomicU64::new(0);
+#[tauri::command]
+fn is_shortcut_enabled(config: State<ShortcutConfig>) -> bool {
+    config.enabled.load(Ordering::SeqCst)
+}
 
-impl WindowService {
-    fn new(provider: Arc<dyn WindowProvider>) -> Self {
-        Self { provider }
+/// Whether the OS would let us register `shortcut`, probed with a transient registration.
+/// Our own active shortcut counts as taken and is left untouched.
+#[tauri::command]
+fn is_shortcut_available(app: AppHandle, config: State<ShortcutConfig>, shortcut: String) -> Result<bool, CommandError> {
+    let parsed: Shortcut = shortcut
+        .parse()
+        .map_err(|e| CommandError::new("invalid_shortcut", format!("{:?}", e)))?;
+    let _registration = config.registration.lock().unwrap();
+
+    let global_shortcut = app.global_shortcut();
+    if global_shortcut.is_registered(parsed) {
+        return Ok(false);
     }
-
-    fn list(&self, capture_thumbnails: bool) -> Vec<WindowInfo> {
-        self.provider.list(capture_thumbnails)
+    if global_shortcut.register(parsed).is_err() {
+        return Ok(false);
     }
+    global_shortcut
+        .unregister(parsed)
+        .map_err(|e| CommandError::new("shortcut_unregister_failed", e.to_string()))?;
+    Ok(true)
+}
 
-    fn activate(&self, id: &str) -> Result<(), String> {
-        self.provider.activate(id)
-    }
+/// Capture width of listing thumbnails when `Config::adaptive_thumbnail_width` is off.
+const THUMBNAIL_WIDTH: u32 = 500;
 
-    fn clear_cache(&self) {
-        self.provider.clear_cache()
+/// Clamp of the per-window width derived from `Config::total_thumbnail_pixel_budget`.
+const BUDGET_THUMBNAIL_WIDTH_RANGE: (u32, u32) = (120, 800);
+
+/// Width chosen for the current listing; see `adapt_thumbnail_width`.
+static EFFECTIVE_THUMBNAIL_WIDTH: AtomicU32 = AtomicU32::new(THUMBNAIL_WIDTH);
+
+/// Picks the listing thumbnail width for `window_count` windows and makes it current: tiles
+/// shrink as the grid fills up, so big captures of many windows are not wasted.
+fn adapt_thumbnail_width(window_count: usize) -> u32 {
+    let width = listing_thumbnail_width(window_count);
+    if EFFECTIVE_THUMBNAIL_WIDTH.swap(width, Ordering::SeqCst) != width {
+        log::debug!("[thumbnail] capture width {}px for {} windows", width, window_count);
     }
+    width
 }
 
-fn build_provider() -> Arc<dyn WindowProvider> {
-    #[cfg(target_os = "macos")]
-    {
-        Arc::new(macos::MacWindowProvider::new())
+/// The listing thumbnail width for `window_count` windows under the current config.
+fn listing_thumbnail_width(window_count: usize) -> u32 {
+    let (budget, adaptive) = {
+        let config = config();
+        (config.total_thumbnail_pixel_budget, config.adaptive_thumbnail_width)
+    };
+    if let Some(budget) = budget {
+        budget_thumbnail_width(budget, window_count)
+    } else if !adaptive {
+        THUMBNAIL_WIDTH
+    } else {
+        match window_count {
+            0..=10 => 500,
+            11..=25 => 300,
+            _ => 180,
+        }
     }
+}
 
-    #[cfg(not(target_os = "macos"))]
-    {
-        Arc::new(MockWindowProvider::default())
-    }
+/// Width giving each of `window_count` windows an equal share of `budget` pixels, assuming
+/// the common 16:10 window shape.
+fn budget_thumbnail_width(budget: u64, window_count: usize) -> u32 {
+    let pixels_per_window = budget as f64 / window_count.max(1) as f64;
+    let width = (pixels_per_window * 16.0 / 10.0).sqrt() as u32;
+    let (min, max) = BUDGET_THUMBNAIL_WIDTH_RANGE;
+    width.clamp(min, max)
 }
 
-#[tauri::command]
-fn list_windows(
-    service: State<WindowService>,
-    refresh_cache: Option<bool>,
-    capture_thumbnails: Option<bool>,
-) -> Vec<WindowInfo> {
-    let refresh = refresh_cache.unwrap_or(false);
-    let capture = capture_thumbnails.unwrap_or(true);
+/// Capture width for listing thumbnails, as last chosen by `adapt_thumbnail_width`.
+fn thumbnail_width() -> u32 {
+    EFFECTIVE_THUMBNAIL_WIDTH.load(Ordering::SeqCst)
+}
 
-    println!("[list_windows] refresh_cache={:?} (resolved={}), capture_thumbnails={:?} (resolved={})",
-        refresh_cache, refresh, capture_thumbnails, capture);
+/// Typical size of a quality-80 JPEG of window content, in bytes per pixel.
+const ESTIMATED_JPEG_BYTES_PER_PIXEL: f64 = 0.15;
 
-    if refresh {
-        service.clear_cache();
-    }
-    service.list(capture)
+/// Expected IPC cost of a full refresh, from `estimate_payload_size`. All sizes in bytes.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PayloadEstimate {
+    window_count: usize,
+    /// Capture width the refresh would use.
+    thumbnail_width: u32,
+    /// The `windows:list` JSON.
+    list_bytes: u64,
+    /// All thumbnail (and corner glyph) data URLs, base64 included.
+    thumbnail_bytes: u64,
+    /// Thumbnails come as one `bundle_thumbnails` buffer over the URI scheme, not over IPC.
+    bundled: bool,
+    /// What the refresh pushes over IPC: the list, plus the thumbnails unless bundled.
+    total_ipc_bytes: u64,
 }
 
+/// Estimates what a full refresh would send, from the last listing and the thumbnail settings
+/// alone; nothing is listed or captured.
 #[tauri::command]
-fn activate_window(
-    id: String,
-    service: State<WindowService>,
-    app: tauri::AppHandle,
-) -> Result<(), String> {
-    service.activate(&id)?;
+fn estimate_payload_size(service: State<WindowService>) -> PayloadEstimate {
+    let windows = service.last_emitted.lock().unwrap().windows.clone();
+    let width = listing_thumbnail_width(windows.len());
+    let config = config();
+
+    let list_bytes = serde_json::to_vec(&windows).map(|json| json.len() as u64).unwrap_or(0);
+    let pixels: f64 = windows
+        .iter()
+        .map(|window| {
+            let (w, h) = match (config.thumbnail_aspect, window.bounds) {
+                (Some((aspect_w, aspect_h)), _) if aspect_w > 0 => (width, width * aspect_h / aspect_w),
+                // Captures are at backing resolution, 2x on Retina displays.
+                (_, Some(bounds)) => thumbnail_size((bounds.width * 2.0) as u32, (bounds.height * 2.0) as u32, width),
+                _ => (width, width * 10 / 16),
+            };
+            let glyph = if config.capture_corner_glyph {
+                (CORNER_GLYPH_WIDTH * CORNER_GLYPH_WIDTH) as f64
+            } else {
+                0.0
+            };
+            (w * h) as f64 + glyph
+        })
+        .sum();
+    // base64 turns every 3 bytes into 4.
+    let thumbnail_bytes = (pixels * ESTIMATED_JPEG_BYTES_PER_PIXEL * 4.0 / 3.0) as u64;
+    let bundled = config.bundle_thumbnails;
+
+    PayloadEstimate {
+        window_count: windows.len(),
+        thumbnail_width: width,
+        list_bytes,
+        thumbnail_bytes,
+        bundled,
+        total_ipc_bytes: if bundled { list_bytes } else { list_bytes + thumbnail_bytes },
+    }
+}
 
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.hide();
+/// Thumbnails taken when their window was last focused, for `Config::focus_time_thumbnails`.
+static FOCUS_THUMBNAILS: LazyLock<Mutex<HashMap<i64, EncodedThumbnail>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Delay between activating a window and capturing it, so it has redrawn as frontmost.
+const FOCUS_CAPTURE_DELAY_MS: u64 = 400;
+
+/// Captures one listed window's thumbnail. The flag marks a cached, possibly outdated image;
+/// focus-time images are served as current, since showing them is what that mode asks for.
+fn capture_thumbnail(window_id: i64, max_width: u32) -> Result<(EncodedThumbnail, bool), CaptureError> {
+    if !config().focus_time_thumbnails {
+        return capture_thumbnail_now(window_id, max_width);
     }
+    if let Some(thumbnail) = FOCUS_THUMBNAILS.lock().unwrap().get(&window_id).cloned() {
+        return Ok((thumbnail, false));
+    }
+    // Never focused through us yet: capture once so the tile is not empty.
+    let captured = capture_thumbnail_now(window_id, max_width);
+    if let Ok((thumbnail, false)) = &captured {
+        FOCUS_THUMBNAILS.lock().unwrap().insert(window_id, thumbnail.clone());
+    }
+    captured
+}
 
-    Ok(())
+/// Refreshes the focus-time thumbnail of a just-activated window in the background.
+fn capture_focus_thumbnail(window_id: i64) {
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(FOCUS_CAPTURE_DELAY_MS));
+        if let Ok((thumbnail, false)) = capture_thumbnail_now(window_id, thumbnail_width()) {
+            log::debug!("[thumbnail] window_id={} focus-time thumbnail refreshed", window_id);
+            FOCUS_THUMBNAILS.lock().unwrap().insert(window_id, thumbnail);
+        }
+    });
 }
 
-#[tauri::command]
-fn get_window_thumbnail(window_id: String) -> Option<String> {
-    #[cfg(target_os = "macos")]
-    {
-        let id = window_id.parse::<i64>().ok()?;
-        macos::capture_window_thumbnail(id, 500)
-    }
+/// Output width of a corner glyph, in pixels.
+const CORNER_GLYPH_WIDTH: u32 = 48;
 
-    #[cfg(not(target_os = "macos"))]
-    {
-        None
-    }
+/// `Config::corner_glyph_crop` when `Config::capture_corner_glyph` is on. The glyph is cropped
+/// from the thumbnail's own capture, so it costs no second capture.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn corner_glyph_crop() -> Option<CropRect> {
+    let config = config();
+    config.capture_corner_glyph.then_some(config.corner_glyph_crop)
 }
 
-#[tauri::command]
-fn check_screen_recording_permission() -> bool {
+/// Captures one window's thumbnail on the current platform, bypassing the focus-time cache.
+fn capture_thumbnail_now(window_id: i64, max_width: u32) -> Result<(EncodedThumbnail, bool), CaptureError> {
     #[cfg(target_os = "macos")]
-    {
-        macos::has_screen_recording_permission()
+    if !headless() {
+        return macos::capture_window_thumbnail_or_cached(window_id, max_width);
     }
 
-    #[cfg(not(target_os = "macos"))]
-    {
-        true
-    }
+    let _ = max_width;
+    mock_thumbnail(&window_id.to_string())
+        .map(|thumbnail| (thumbnail, false))
+        .ok_or(CaptureError::EncodeFailed)
 }
 
+/// Captures one thumbnail off the command thread and answers with `thumbnail:ready`, which the
+/// frontend correlates by `request_id`. A refresh started in the meantime cancels the reply.
 #[tauri::command]
-fn log_debug(msg: String) {
-    println!("{}", msg);
+fn request_thumbnail(window_id: String, request_id: String, app: AppHandle) -> Result<(), CommandError> {
+    let id = app
+        .state::<WindowService>()
+        .os_id(&window_id)
+        .parse::<i64>()
+        .map_err(|_| CommandError::new("invalid_window_id", format!("window id {window_id} is not numeric")))?;
+    let current_gen = REFRESH_GENERATION.load(Ordering::SeqCst);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
+            return;
+        }
+        let captured = capture_thumbnail(id, thumbnail_width());
+        if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
+            log::debug!("[thumbnail] request {} stale after capture (gen {})", request_id, current_gen);
+            return;
+        }
+        let (thumbnail, stale) = match captured {
+            Ok((thumbnail, stale)) => (Some(thumbnail.data_url()), stale),
+            Err(_) => (None, false),
+        };
+        let payload = serde_json::json!({
+            "requestId": request_id,
+            "id": window_id,
+            "thumbnail": thumbnail,
+            "stale": stale
+        });
+        let _ = app.emit("thumbnail:ready", payload);
+    });
+
+    Ok(())
 }
 
-#[tauri::command]
-fn switch_to_english_input() {
-    #[cfg(target_os = "macos")]
-    {
-        macos::switch_to_english_input();
+/// Captures one window's thumbnail on the blocking pool and emits it as `window:thumbnail`,
+/// unless a newer refresh than `current_gen` started. `None` for non-numeric ids.
+fn spawn_thumbnail_capture(
+    app: &AppHandle,
+    window_id: &str,
+    current_gen: u64,
+) -> Option<tauri::async_runtime::JoinHandle<()>> {
+    let id = app.state::<WindowService>().os_id(window_id).parse::<i64>().ok()?;
+    let window_id = window_id.to_string();
+    let app = app.clone();
+
+    Some(tauri::async_runtime::spawn_blocking(move || {
+        capture_and_emit_thumbnail(&app, id, &window_id, current_gen)
+    }))
+}
+
+/// Body of `spawn_thumbnail_capture`: captures OS window `id` (public id `window_id`) and emits
+/// the result, unless a newer refresh than `current_gen` started.
+fn capture_and_emit_thumbnail(app: &AppHandle, id: i64, window_id: &str, current_gen: u64) {
+    // Check if still current before doing expensive work
+    if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
+        return;
+    }
+
+    // Stamped before capturing: a capture that started later is newer, whenever it finishes
+    let version = THUMBNAIL_VERSION.fetch_add(1, Ordering::SeqCst) + 1;
+    match capture_thumbnail(id, thumbnail_width()) {
+        Ok((thumbnail, stale)) => {
+            // Check before emitting
+            if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
+                return;
+            }
+            if !app.state::<WindowService>().remember_emitted_thumbnail(window_id, &thumbnail, stale, version) {
+                let _ = app.emit("window:thumbnail-unchanged", serde_json::json!({ "id": window_id }));
+                return;
+            }
+            let mut payload = serde_json::json!({
+                "id": window_id,
+                "thumbnail": thumbnail.data_url(),
+                "stale": stale,
+                "version": version
+            });
+            if let Some(glyph) = &thumbnail.corner_glyph {
+                payload["cornerGlyph"] = glyph.clone().into();
+            }
+            let _ = app.emit("window:thumbnail", payload);
+        }
+        Err(reason) => {
+            if REFRESH_GENERATION.load(Ordering::SeqCst) == current_gen {
+                let _ = app.emit("window:thumbnail-failed", serde_json::json!({ "id": window_id, "reason": reason }));
+            }
+        }
     }
 }
 
+/// Clears one kind of cache (`thumbnails`, `list`, `snapshot` or `all`) without refreshing,
+/// a finer tool than `list_windows(refresh_cache: true)`.
 #[tauri::command]
-fn get_shortcut(config: State<ShortcutConfig>) -> String {
-    config.current.lock().unwrap().clone()
+fn invalidate(kind: String, service: State<WindowService>) -> Result<(), CommandError> {
+    let cache_kind: CacheKind = serde_json::from_value(serde_json::Value::String(kind.clone())).map_err(|_| {
+        CommandError::new("invalid_cache_kind", format!("unknown cache kind {kind:?}"))
+            .with_details(serde_json::json!({ "kind": kind }))
+    })?;
+    service.invalidate(cache_kind);
+    log::debug!("[rifthold] invalidated {:?} cache", cache_kind);
+    Ok(())
 }
 
+/// Re-emits the last `windows:list` and every thumbnail sent for it since, without listing or
+/// capturing anything. Lets a reloaded webview repopulate instantly.
 #[tauri::command]
-fn set_shortcut(app: AppHandle, config: State<ShortcutConfig>, shortcut: String) -> Result<(), String> {
-    app.global_shortcut().unregister_all().map_err(|e| e.to_string())?;
-
-    let parsed: Shortcut = shortcut.parse().map_err(|e| format!("{:?}", e))?;
-
-    app.global_shortcut()
-        .on_shortcut(parsed, move |app, _shortcut, event| {
-            if event.state == ShortcutState::Pressed {
-                let _ = toggle_overlay(app);
-            }
-        })
-        .map_err(|e| e.to_string())?;
+fn resend_last_list(service: State<WindowService>, app: AppHandle) -> Result<(), CommandError> {
+    emit_last_list(&service, &app)?;
+    Ok(())
+}
 
-    *config.current.lock().unwrap() = shortcut.clone();
-    save_config(&Config { shortcut })?;
+fn emit_last_list(service: &WindowService, app: &AppHandle) -> tauri::Result<()> {
+    let emitted = service.last_emitted.lock().unwrap();
+    let displayed = group_windows(emitted.windows.clone(), config().group_mode);
+    service.remember_displayed(&displayed);
+    app.emit("windows:list", displayed)?;
+    // Resent with their original versions, so a replay never overrides a newer capture.
+    for (id, (thumbnail, stale, version)) in &emitted.thumbnails {
+        let mut payload = serde_json::json!({
+            "id": id,
+            "thumbnail": thumbnail.data_url(),
+            "stale": stale,
+            "version": version
+        });
+        if let Some(glyph) = &thumbnail.corner_glyph {
+            payload["cornerGlyph"] = glyph.clone().into();
+        }
+        app.emit("window:thumbnail", payload)?;
+    }
+    app.emit("windows:thumbnails-complete", ())?;
     Ok(())
 }
 
+/// Lazy-thumbnail mode: captures only the given (visible) windows of the current refresh.
+/// Results arrive as `window:thumbnail` events, like a full refresh.
+#[tauri::command]
+fn request_thumbnails_in_range(ids: Vec<String>, app: AppHandle) {
+    let current_gen = REFRESH_GENERATION.load(Ordering::SeqCst);
+    log::debug!("[thumbnail] lazy request for {} windows (gen {})", ids.len(), current_gen);
+    for id in &ids {
+        let _ = spawn_thumbnail_capture(&app, id, current_gen);
+    }
+}
+
 #[tauri::command]
-async fn refresh_windows_async(app: tauri::AppHandle, service: State<'_, WindowService>) -> Result<(), String> {
+async fn refresh_windows_async(
+    app: tauri::AppHandle,
+    service: State<'_, WindowService>,
+    force_thumbnails: Option<bool>,
+) -> Result<(), CommandError> {
     // Increment generation to cancel any in-flight tasks
     let current_gen = REFRESH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
 
-    // Clone the provider Arc to move into spawned task
-    let provider = service.provider.clone();
+    // A frontend that starts empty (first load, reload) needs every thumbnail again
+    if force_thumbnails.unwrap_or(false) {
+        service.forget_thumbnail_hashes();
+    }
+
+    // Clone the service handles to move into spawned task
+    let service = service.inner().clone();
 
     // Spawn the entire refresh operation to avoid blocking the main thread
     tauri::async_runtime::spawn(async move {
@@ -254,118 +3360,536 @@ async fn refresh_windows_async(app: tauri::AppHandle, service: State<'_, WindowS
             return;
         }
 
+        // Cold start: show the previous session's windows while the first listing runs
+        let persisted = PERSISTED_LIST.lock().unwrap().take();
+        if let Some(persisted) = persisted {
+            let _ = app.emit("windows:list", group_windows(persisted, config().group_mode));
+        }
+
         // Get window list in a blocking task (it calls CoreGraphics APIs)
+        let list_service = service.clone();
         let windows = tauri::async_runtime::spawn_blocking(move || {
-            provider.list(false)
+            list_service.list(false)
         }).await.unwrap_or_default();
 
         // Check again after getting window list
         if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
-            println!("[thumbnail] stale after list fetch (gen {})", current_gen);
+            log::debug!("[thumbnail] stale after list fetch (gen {})", current_gen);
             return;
         }
 
         // Emit window list immediately
-        let _ = app.emit("windows:list", &windows);
+        service.remember_emitted_list(&windows);
+        let displayed = group_windows(windows.clone(), config().group_mode);
+        service.remember_displayed(&displayed);
+        let _ = app.emit("windows:list", displayed);
+        record_open_latency(&app);
+
+        stream_thumbnails(app, windows, current_gen).await;
+    });
+
+    Ok(())
+}
+
+/// Delivers thumbnails for `windows` as `window:thumbnail` events followed by
+/// `windows:thumbnails-complete`, or just `windows:thumbnails-deferred` in lazy mode.
+async fn stream_thumbnails(app: AppHandle, windows: Vec<WindowInfo>, current_gen: u64) {
+    if config().lazy_thumbnails {
+        let _ = app.emit("windows:thumbnails-deferred", ());
+        return;
+    }
+
+    if config().bundle_thumbnails {
+        bundle_thumbnails(app, windows, current_gen).await;
+        return;
+    }
+
+    let batch_start = std::time::Instant::now();
+
+    // Top of the list first: the low-priority pass only starts once these are emitted
+    let split = config().priority_thumbnails.min(windows.len());
+    let (high, low) = windows.split_at(split);
+    capture_thumbnail_pass(&app, high, current_gen).await;
+    if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
+        return;
+    }
+    if !high.is_empty() {
+        log::debug!("[thumbnail] {} priority thumbnails in {}ms (gen {})", high.len(), batch_start.elapsed().as_millis(), current_gen);
+    }
+    capture_thumbnail_pass(&app, low, current_gen).await;
+
+    // Only emit completion if this is still the current generation
+    if REFRESH_GENERATION.load(Ordering::SeqCst) == current_gen {
+        emit_thumbnails_complete(&app, windows.len(), batch_start, current_gen);
+    }
+}
+
+/// Captures and emits thumbnails for `windows`, returning once all of them are done.
+async fn capture_thumbnail_pass(app: &AppHandle, windows: &[WindowInfo], current_gen: u64) {
+    // On battery with a CPU budget, captures share the budget-wide pool
+    if let Some(pool) = thumbnail_pool() {
+        use rayon::prelude::*;
+
+        let service = app.state::<WindowService>().inner().clone();
+        let targets: Vec<(i64, String)> = windows
+            .iter()
+            .filter_map(|window| Some((service.os_id(&window.id).parse::<i64>().ok()?, window.id.clone())))
+            .collect();
+        let app = app.clone();
+        let _ = tauri::async_runtime::spawn_blocking(move || {
+            pool.install(|| {
+                targets
+                    .par_iter()
+                    .for_each(|(id, window_id)| capture_and_emit_thumbnail(&app, *id, window_id, current_gen))
+            })
+        })
+        .await;
+        return;
+    }
+
+    // Otherwise spawn thumbnail tasks in parallel for maximum speed
+    let tasks: Vec<_> = windows
+        .iter()
+        .filter_map(|window| spawn_thumbnail_capture(app, &window.id, current_gen))
+        .collect();
+
+    // Wait for all tasks (they will self-cancel via generation check)
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+fn emit_thumbnails_complete(app: &AppHandle, window_count: usize, batch_start: std::time::Instant, current_gen: u64) {
+    let total_elapsed = batch_start.elapsed().as_millis();
+    log::debug!("[thumbnail] batch complete: {} windows in {}ms (gen {})", window_count, total_elapsed, current_gen);
+    let _ = app.emit("windows:thumbnails-complete", ());
+    let metrics = serde_json::json!({
+        "windowCount": window_count,
+        "thumbnailWidth": thumbnail_width(),
+        "pixelBudget": config().total_thumbnail_pixel_budget,
+        "thumbnailMs": total_elapsed as u64,
+    });
+    *LAST_LIST_METRICS.lock().unwrap() = Some(metrics.clone());
+    let _ = app.emit("windows:metrics", metrics);
+}
+
+/// Payload of the latest `windows:metrics` event, for `collect_diagnostics`.
+static LAST_LIST_METRICS: Mutex<Option<serde_json::Value>> = Mutex::new(None);
 
-        let batch_start = std::time::Instant::now();
+/// URI scheme serving thumbnail bundles, as `rifthold://localhost/thumbnails-<generation>`.
+const THUMBNAIL_BUNDLE_SCHEME: &str = "rifthold";
 
-        // Spawn all thumbnail tasks in parallel for maximum speed
-        let mut tasks = Vec::with_capacity(windows.len());
-        for window in windows.iter() {
-            if let Ok(window_id) = window.id.parse::<i64>() {
-                let window_id_str = window.id.clone();
-                let app_clone = app.clone();
+/// Latest thumbnail bundle and the refresh generation it was captured for.
+static THUMBNAIL_BUNDLE: Mutex<Option<(u64, Vec<u8>)>> = Mutex::new(None);
+
+/// `Config::bundle_thumbnails` delivery: captures every window, stores the packed images
+/// for the `rifthold` scheme and announces them with `windows:thumbnails-bundle
+/// { generation, count, cornerGlyphs }`. No per-window `window:thumbnail` events are sent.
+async fn bundle_thumbnails(app: AppHandle, windows: Vec<WindowInfo>, current_gen: u64) {
+    use rayon::prelude::*;
 
-                let task = tauri::async_runtime::spawn_blocking(move || {
-                    // Check if still current before doing expensive work
+    let batch_start = std::time::Instant::now();
+    let window_count = windows.len();
+    let service = app.state::<WindowService>().inner().clone();
+    let entries = tauri::async_runtime::spawn_blocking(move || {
+        let width = thumbnail_width();
+        let capture_all = || {
+            windows
+                .par_iter()
+                .filter_map(|window| {
                     if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
-                        return;
+                        return None;
                     }
+                    let os_id = service.os_id(&window.id).parse::<i64>().ok()?;
+                    let version = THUMBNAIL_VERSION.fetch_add(1, Ordering::SeqCst) + 1;
+                    let (thumbnail, stale) = capture_thumbnail(os_id, width).ok()?;
+                    service.remember_emitted_thumbnail(&window.id, &thumbnail, stale, version);
+                    let flags = u8::from(stale) | (u8::from(thumbnail.png) << 1);
+                    Some(((window.id.clone(), thumbnail.bytes, flags), thumbnail.corner_glyph))
+                })
+                .collect::<Vec<_>>()
+        };
+        // On battery with a CPU budget, run on the shared budget-wide pool.
+        match thumbnail_pool() {
+            Some(pool) => pool.install(capture_all),
+            None => capture_all(),
+        }
+    })
+    .await
+    .unwrap_or_default();
 
-                    #[cfg(target_os = "macos")]
-                    {
-                        if let Some(thumbnail) = macos::capture_window_thumbnail(window_id, 500) {
-                            // Check before emitting
-                            if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
-                                return;
-                            }
-                            let payload = serde_json::json!({
-                                "id": window_id_str,
-                                "thumbnail": thumbnail
-                            });
-                            let _ = app_clone.emit("window:thumbnail", payload);
-                        }
-                    }
-                });
-                tasks.push(task);
-            }
+    if REFRESH_GENERATION.load(Ordering::SeqCst) != current_gen {
+        return;
+    }
+    // Glyphs are tiny; they ride along in the event rather than in the bundle format.
+    let corner_glyphs: HashMap<String, String> = entries
+        .iter()
+        .filter_map(|((id, _, _), glyph)| Some((id.clone(), glyph.clone()?)))
+        .collect();
+    let entries: Vec<(String, Vec<u8>, u8)> = entries.into_iter().map(|(entry, _)| entry).collect();
+    let count = entries.len();
+    *THUMBNAIL_BUNDLE.lock().unwrap() = Some((current_gen, encode_thumbnail_bundle(&entries)));
+    let _ = app.emit(
+        "windows:thumbnails-bundle",
+        serde_json::json!({ "generation": current_gen, "count": count, "cornerGlyphs": corner_glyphs }),
+    );
+    emit_thumbnails_complete(&app, window_count, batch_start, current_gen);
+}
+
+/// Packs `(window id, image, flags)` entries into one buffer. All integers are little-endian:
+///
+/// ```text
+/// offset  size     field
+/// 0       4        magic "RTHB"
+/// 4       u32      format version, currently 1
+/// 8       u32      entry count N
+/// 12      ...      N index entries, back to back:
+///                    u16      id length L, in bytes
+///                    L        window id, UTF-8
+///                    u32      image offset, from the start of the buffer
+///                    u32      image length, in bytes
+///                    u8       flags: bit 0 stale, bit 1 PNG (else JPEG)
+/// ...     ...      the images, back to back in index order
+/// ```
+fn encode_thumbnail_bundle(entries: &[(String, Vec<u8>, u8)]) -> Vec<u8> {
+    const VERSION: u32 = 1;
+
+    let index_len: usize = entries.iter().map(|(id, _, _)| 2 + id.len() + 4 + 4 + 1).sum();
+    let images_len: usize = entries.iter().map(|(_, image, _)| image.len()).sum();
+    let mut bundle = Vec::with_capacity(12 + index_len + images_len);
+    bundle.extend_from_slice(b"RTHB");
+    bundle.extend_from_slice(&VERSION.to_le_bytes());
+    bundle.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+
+    let mut offset = 12 + index_len;
+    for (id, image, flags) in entries {
+        bundle.extend_from_slice(&(id.len() as u16).to_le_bytes());
+        bundle.extend_from_slice(id.as_bytes());
+        bundle.extend_from_slice(&(offset as u32).to_le_bytes());
+        bundle.extend_from_slice(&(image.len() as u32).to_le_bytes());
+        bundle.push(*flags);
+        offset += image.len();
+    }
+    for (_, image, _) in entries {
+        bundle.extend_from_slice(image);
+    }
+    bundle
+}
+
+/// Serves the bundle for `thumbnails-<generation>`; 404 once a newer refresh replaced it.
+fn serve_thumbnail_bundle(path: &str) -> tauri::http::Response<Vec<u8>> {
+    let generation = path
+        .trim_start_matches('/')
+        .strip_prefix("thumbnails-")
+        .and_then(|generation| generation.parse::<u64>().ok());
+    let bundle = THUMBNAIL_BUNDLE.lock().unwrap();
+    let response = tauri::http::Response::builder().header("Access-Control-Allow-Origin", "*");
+    match (bundle.as_ref(), generation) {
+        (Some((bundled, bytes)), Some(wanted)) if *bundled == wanted => response
+            .header("Content-Type", "application/octet-stream")
+            .body(bytes.clone()),
+        _ => response.status(404).body(Vec::new()),
+    }
+    .unwrap_or_default()
+}
+
+fn fit_to_current_workspace<R: Runtime>(
+    app: &AppHandle<R>,
+    window: &WebviewWindow<R>,
+) -> tauri::Result<()> {
+    let pinned = config().overlay_display.and_then(|display_id| pinned_monitor(app, display_id));
+    let monitor = match pinned {
+        Some(monitor) => Some(monitor),
+        None => window.current_monitor()?.or(app.primary_monitor()?),
+    };
+    if let Some(monitor) = monitor {
+        let scale = monitor.scale_factor();
+        let size = monitor.size().to_logical::<f64>(scale);
+        let position = monitor.position().to_logical::<f64>(scale);
+
+        window.set_size(LogicalSize::new(size.width, size.height))?;
+        window.set_position(LogicalPosition::new(position.x, position.y))?;
+    }
+    Ok(())
+}
+
+/// The monitor of `Config::overlay_display`, matched to the CG display by origin. `None`
+/// (logged) when that display is no longer attached.
+fn pinned_monitor<R: Runtime>(app: &AppHandle<R>, display_id: u32) -> Option<tauri::Monitor> {
+    let Some(display) = displays().into_iter().find(|display| display.id == display_id) else {
+        log::warn!("[overlay] pinned display {} not attached, following the current monitor", display_id);
+        return None;
+    };
+    app.available_monitors().ok()?.into_iter().find(|monitor| {
+        let position = monitor.position().to_logical::<f64>(monitor.scale_factor());
+        (position.x - display.bounds.x).abs() < 1.0 && (position.y - display.bounds.y).abs() < 1.0
+    })
+}
+
+fn focus_overlay<R: Runtime>(app: &AppHandle<R>, window: &WebviewWindow<R>) -> tauri::Result<()> {
+    let (sequence, fade_ms) = {
+        let config = config();
+        (config.show_sequence, config.fade_ms)
+    };
+    if fade_ms > 0 {
+        set_overlay_alpha(window, 0.0);
+    }
+
+    match sequence {
+        ShowSequence::ShowThenSize => {
+            // Show window first for instant visibility
+            window.show()?;
+            window.unminimize()?;
+
+            // Then immediately adjust size and position
+            fit_overlay(app, window)?;
+        }
+        ShowSequence::SizeThenShow => {
+            // Place it while hidden so it appears at its final frame
+            fit_overlay(app, window)?;
+            window.show()?;
+            window.unminimize()?;
         }
+    }
+
+    if fade_ms > 0 {
+        fade_in_overlay(window, fade_ms);
+    }
+    window.set_focus()?;
+    Ok(())
+}
+
+/// Sets the overlay's opacity, on the main thread (queued behind earlier window calls).
+fn set_overlay_alpha<R: Runtime>(window: &WebviewWindow<R>, alpha: f64) {
+    #[cfg(target_os = "macos")]
+    if let Ok(ns_window) = window.ns_window() {
+        let ns_window = ns_window as usize;
+        let _ = window.run_on_main_thread(move || macos::set_window_alpha(ns_window as _, alpha));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    let _ = (window, alpha);
+}
 
-        // Wait for all tasks (they will self-cancel via generation check)
-        for task in tasks {
-            let _ = task.await;
+/// Animates the overlay's opacity up to 1 over `duration_ms`.
+fn fade_in_overlay<R: Runtime>(window: &WebviewWindow<R>, duration_ms: u64) {
+    #[cfg(target_os = "macos")]
+    if let Ok(ns_window) = window.ns_window() {
+        let ns_window = ns_window as usize;
+        let _ = window.run_on_main_thread(move || macos::fade_in_window(ns_window as _, duration_ms));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    let _ = (window, duration_ms);
+}
+
+/// Bundle id the overlay is attached to by `attach_overlay_to_app`, if any.
+static ATTACHED_APP: Mutex<Option<String>> = Mutex::new(None);
+
+/// Frame of the frontmost listed window of `bundle_id`.
+fn front_window_bounds<R: Runtime>(app: &AppHandle<R>, bundle_id: &str) -> Option<WindowBounds> {
+    let service = app.state::<WindowService>();
+    let window = service.list_app(bundle_id, false).into_iter().next()?;
+    service.window_bounds(&window.id).or(window.bounds)
+}
+
+/// Covers the attached app's front window (re-read each time, as it may have moved), or the
+/// whole workspace when detached or when that app has no window left.
+fn fit_overlay<R: Runtime>(app: &AppHandle<R>, window: &WebviewWindow<R>) -> tauri::Result<()> {
+    let attached = ATTACHED_APP.lock().unwrap().clone();
+    if let Some(bounds) = attached.and_then(|bundle_id| front_window_bounds(app, &bundle_id)) {
+        // Not pinned above everything: it sits over its app and yields to the others.
+        pin_overlay(window, false)?;
+        window.set_size(LogicalSize::new(bounds.width, bounds.height))?;
+        window.set_position(LogicalPosition::new(bounds.x, bounds.y))?;
+        return Ok(());
+    }
+    pin_overlay(window, config().always_on_top)?;
+    fit_to_current_workspace(app, window)
+}
+
+/// Keeps the overlay above other windows at `Config::overlay_level`, or returns it to the
+/// normal level. On macOS the window level is set explicitly rather than through the generic
+/// always-on-top flag, whose level is not ours to choose.
+fn pin_overlay<R: Runtime>(window: &WebviewWindow<R>, pinned: bool) -> tauri::Result<()> {
+    #[cfg(target_os = "macos")]
+    if let Ok(ns_window) = window.ns_window() {
+        let level = if pinned { config().overlay_level } else { OverlayLevel::Normal };
+        let ns_window = ns_window as usize;
+        return window.run_on_main_thread(move || macos::set_window_level(ns_window as _, level.ns_level()));
+    }
+
+    window.set_always_on_top(pinned)
+}
+
+fn emit_overview_show<R: Runtime>(app: &AppHandle<R>) {
+    let _ = app.emit("overview:show", ());
+}
+
+/// Interval of the `Config::live_titles` poll.
+const LIVE_TITLE_POLL_MS: u64 = 1000;
+
+/// Bumped whenever the overlay shows or hides; a title poll stops once it is outdated.
+static TITLE_POLL_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Polls listed titles while the overlay stays open, emitting `window:title-changed` for each
+/// title that differs from what the frontend was sent.
+fn start_title_poll<R: Runtime>(app: &AppHandle<R>) {
+    let generation = TITLE_POLL_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    if !config().live_titles {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(std::time::Duration::from_millis(LIVE_TITLE_POLL_MS));
+        if TITLE_POLL_GENERATION.load(Ordering::SeqCst) != generation || !config().live_titles {
+            return;
+        }
+        for (id, title) in app.state::<WindowService>().changed_titles() {
+            log::debug!("[rifthold] title of window {} changed to {:?}", id, title);
+            let _ = app.emit("window:title-changed", serde_json::json!({ "id": id, "title": title }));
         }
+    });
+}
+
+/// Hides the main window and tells the frontend via `overview:hide`.
+fn hide_main_overlay<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<()> {
+    TITLE_POLL_GENERATION.fetch_add(1, Ordering::SeqCst);
+    if let Some(window) = app.get_webview_window("main") {
+        window.hide()?;
+    }
+    let _ = app.emit("overview:hide", ());
+    Ok(())
+}
 
-        // Only emit completion if this is still the current generation
-        if REFRESH_GENERATION.load(Ordering::SeqCst) == current_gen {
-            let total_elapsed = batch_start.elapsed().as_millis();
-            println!("[thumbnail] batch complete: {} windows in {}ms (gen {})", windows.len(), total_elapsed, current_gen);
-            let _ = app.emit("windows:thumbnails-complete", ());
+fn toggle_overlay<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<()> {
+    if let Some(window) = app.get_webview_window("main") {
+        if window.is_visible()? {
+            hide_main_overlay(app)?;
+        } else {
+            show_main_overlay(app)?;
         }
-    });
-
+    }
     Ok(())
 }
 
-fn fit_to_current_workspace<R: Runtime>(
-    app: &AppHandle<R>,
-    window: &WebviewWindow<R>,
-) -> tauri::Result<()> {
-    let monitor = window.current_monitor()?.or(app.primary_monitor()?);
-    if let Some(monitor) = monitor {
-        let scale = monitor.scale_factor();
-        let size = monitor.size().to_logical::<f64>(scale);
-        let position = monitor.position().to_logical::<f64>(scale);
-
-        window.set_size(LogicalSize::new(size.width, size.height))?;
-        window.set_position(LogicalPosition::new(position.x, position.y))?;
+/// Shows and focuses the main window and asks the frontend to refresh via `overview:show`.
+fn show_main_overlay<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<()> {
+    if let Some(window) = app.get_webview_window("main") {
+        *OPEN_STARTED_AT.lock().unwrap() = Some(std::time::Instant::now());
+        focus_overlay(app, &window)?;
+        emit_overview_show(app);
+        start_title_poll(app);
     }
     Ok(())
 }
 
-fn focus_overlay<R: Runtime>(app: &AppHandle<R>, window: &WebviewWindow<R>) -> tauri::Result<()> {
-    // Show window first for instant visibility
-    window.show()?;
-    window.unminimize()?;
-    window.set_always_on_top(true)?;
+/// Records a press and reports whether it is far enough from the previous accepted one.
+/// `last_press_ms` is the Unix time in milliseconds of that press, kept per registered
+/// shortcut so that different actions pressed in quick succession all run.
+fn accept_shortcut_press(last_press_ms: &AtomicU64) -> bool {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0);
+    let debounce_ms = config().shortcut_debounce_ms;
+    let previous = last_press_ms.load(Ordering::SeqCst);
+    if now.saturating_sub(previous) < debounce_ms {
+        log::debug!("[rifthold] shortcut press ignored ({}ms after the last)", now.saturating_sub(previous));
+        return false;
+    }
+    last_press_ms.store(now, Ordering::SeqCst);
+    true
+}
 
-    // Then immediately adjust size and position
-    fit_to_current_workspace(app, window)?;
+/// What a global shortcut does; the names are the keys of `Config::shortcuts`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShortcutAction {
+    Toggle,
+    ActivatePrevious,
+    QuickSwitch,
+    /// 0-based index; configured as `activate_nth_1` for the first tile.
+    ActivateNth(usize),
+}
 
-    window.set_focus()?;
-    Ok(())
+impl ShortcutAction {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "toggle" => Some(ShortcutAction::Toggle),
+            "activate_previous" => Some(ShortcutAction::ActivatePrevious),
+            "quick_switch" => Some(ShortcutAction::QuickSwitch),
+            _ => name
+                .strip_prefix("activate_nth_")
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|&n| n >= 1)
+                .map(|n| ShortcutAction::ActivateNth(n - 1)),
+        }
+    }
 }
 
-fn emit_overview_show<R: Runtime>(app: &AppHandle<R>) {
-    let _ = app.emit("overview:show", ());
+fn run_shortcut_action(app: &AppHandle, action: ShortcutAction) {
+    let result = match action {
+        ShortcutAction::Toggle => toggle_overlay(app).map_err(CommandError::from),
+        ShortcutAction::ActivatePrevious => activate_previous_window(app.state(), app.clone()).map(|_| ()),
+        ShortcutAction::QuickSwitch => quick_switch(app.state()).map(|_| ()),
+        ShortcutAction::ActivateNth(index) => activate_nth_window(index, app.state(), app.clone()).map(|_| ()),
+    };
+    if let Err(error) = result {
+        log::warn!("[rifthold] shortcut action {:?} failed: {}", action, error);
+    }
 }
 
-fn toggle_overlay<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<()> {
-    if let Some(window) = app.get_webview_window("main") {
-        if window.is_visible()? {
-            window.hide()?;
-        } else {
-            focus_overlay(app, &window)?;
-            emit_overview_show(app);
+/// Registers `shortcut` to run `action`, debounced by `Config::shortcut_debounce_ms` against
+/// its own previous press.
+fn register_action_shortcut(
+    app: &AppHandle,
+    shortcut: Shortcut,
+    action: ShortcutAction,
+) -> Result<(), tauri_plugin_global_shortcut::Error> {
+    let last_press_ms = AtomicU64::new(0);
+    app.global_shortcut().on_shortcut(shortcut, move |app, _shortcut, event| {
+        if event.state == ShortcutState::Pressed && accept_shortcut_press(&last_press_ms) {
+            run_shortcut_action(app, action);
+        }
+    })
+}
+
+/// Registers the overlay toggle unless `None` (suppressed; failure is an error) and every
+/// other `Config::shortcuts` entry (failures and unknown action names are logged and skipped).
+fn register_configured_shortcuts(
+    app: &AppHandle,
+    toggle: Option<Shortcut>,
+) -> Result<(), tauri_plugin_global_shortcut::Error> {
+    if let Some(toggle) = toggle {
+        register_action_shortcut(app, toggle, ShortcutAction::Toggle)?;
+    }
+
+    let shortcuts = config().shortcuts.clone();
+    for (name, combo) in shortcuts {
+        let Some(action) = ShortcutAction::from_name(&name) else {
+            log::warn!("[rifthold] unknown shortcut action {name:?} (combo {combo:?}) in config; ignored");
+            continue;
+        };
+        if action == ShortcutAction::Toggle {
+            continue;
+        }
+        let parsed = match combo.parse::<Shortcut>() {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                log::warn!("[rifthold] invalid combo {combo:?} for shortcut action {name:?}: {error:?}");
+                continue;
+            }
+        };
+        if let Err(error) = register_action_shortcut(app, parsed, action) {
+            log::warn!("[rifthold] failed to register {combo:?} for shortcut action {name:?}: {error}");
         }
     }
     Ok(())
 }
 
-fn register_shortcuts<R: Runtime>(app: &tauri::App<R>) -> tauri::Result<()> {
+fn register_shortcuts(app: &tauri::App) -> tauri::Result<()> {
     #[cfg(target_os = "macos")]
-    {
+    if !headless() {
         use cocoa::appkit::{NSApplication, NSApplicationActivationPolicy};
         unsafe {
             let ns_app = cocoa::appkit::NSApp();
@@ -375,16 +3899,10 @@ fn register_shortcuts<R: Runtime>(app: &tauri::App<R>) -> tauri::Result<()> {
 
     app.handle().plugin(tauri_plugin_global_shortcut::Builder::new().build())?;
 
-    let config = load_config();
-    let shortcut: Shortcut = config.shortcut.parse()
+    let shortcut: Shortcut = config().toggle_shortcut().parse()
         .map_err(|e| tauri::Error::PluginInitialization("global-shortcut".into(), format!("{:?}", e)))?;
 
-    app.global_shortcut()
-        .on_shortcut(shortcut, |app, _shortcut, event| {
-            if event.state == ShortcutState::Pressed {
-                let _ = toggle_overlay(app);
-            }
-        })
+    register_configured_shortcuts(app.handle(), Some(shortcut))
         .map_err(|e| tauri::Error::PluginInitialization("global-shortcut".into(), e.to_string()))?;
 
     if let Some(window) = app.get_webview_window("main") {
@@ -396,63 +3914,307 @@ fn register_shortcuts<R: Runtime>(app: &tauri::App<R>) -> tauri::Result<()> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logging::init();
     let provider = build_provider();
-    let config = load_config();
+    let shortcut = config().toggle_shortcut().to_string();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .manage(WindowService::new(provider))
+        .register_uri_scheme_protocol(THUMBNAIL_BUNDLE_SCHEME, |_ctx, request| {
+            serve_thumbnail_bundle(request.uri().path())
+        })
         .manage(ShortcutConfig {
-            current: Mutex::new(config.shortcut),
+            current: Mutex::new(shortcut),
+            registration: Mutex::new(()),
+            enabled: AtomicBool::new(true),
         })
         .invoke_handler(tauri::generate_handler![
             list_windows,
+            list_app_windows,
+            search_windows,
+            export_windows_json,
             activate_window,
+            activate_app_all_windows,
+            activate_nth_window,
+            activate_previous_window,
+            quick_switch,
+            activate_or_launch,
+            peek_window,
+            hide_overlay,
+            is_overlay_visible,
+            set_always_on_top,
+            attach_overlay_to_app,
+            detach_overlay,
+            list_spaces,
+            switch_to_space,
+            dump_ax_tree,
+            set_provider_mode,
+            get_app_priority,
+            set_app_priority,
+            resolve_window_key,
+            flag_window,
+            save_layout,
+            restore_layout,
+            list_layouts,
             get_window_thumbnail,
+            get_window_thumbnail_or_cached,
+            capture_by_raw_id,
+            capture_overlay_self,
+            get_window_thumbnail_sizes,
+            capture_with_fallback,
+            request_thumbnail,
+            request_thumbnails_in_range,
+            resend_last_list,
+            invalidate,
+            set_group_mode,
             refresh_windows_async,
             get_shortcut,
+            is_shortcut_available,
+            set_shortcut_enabled,
+            reregister_shortcuts,
+            is_shortcut_enabled,
             set_shortcut,
             check_screen_recording_permission,
+            collect_diagnostics,
+            get_last_open_latency,
+            get_power_state,
+            capture_backends,
+            estimate_payload_size,
+            get_displays,
+            set_overlay_display,
+            get_overlay_display,
+            window_at_point,
+            find_similar_windows,
+            wait_for_ready,
+            repair_config,
             switch_to_english_input,
-            log_debug
+            log_debug,
+            set_log_level,
+            get_log_level
         ])
         .setup(|app| {
+            if headless() {
+                log::info!("[rifthold] {HEADLESS_ENV}=1: using the mock provider, native calls disabled");
+            }
+            if let Some(error) = CONFIG_LOAD_ERROR.lock().unwrap().clone() {
+                let _ = app.emit(
+                    "config:error",
+                    serde_json::json!({ "path": config_path().display().to_string(), "error": error }),
+                );
+            }
+            #[cfg(target_os = "macos")]
+            let detected = if headless() { None } else { macos::main_bundle_id() };
+            #[cfg(not(target_os = "macos"))]
+            let detected: Option<String> = None;
+            let detected = detected.unwrap_or_else(|| app.config().identifier.clone());
+            log::debug!("[rifthold] detected self bundle id: {detected}");
+            let _ = DETECTED_SELF_BUNDLE_ID.set(detected);
+
             // Warm up the window list API in background to avoid first-call latency
-            let provider = app.state::<WindowService>().provider.clone();
-            std::thread::spawn(move || {
-                let _ = provider.list(false);
-                println!("[rifthold] window list API warmed up");
-            });
-            register_shortcuts(app).map_err(Into::into)
+            if config().warm_up_on_start {
+                let provider = app.state::<WindowService>().provider();
+                std::thread::spawn(move || {
+                    let _ = provider.list(false);
+                    log::info!("[rifthold] window list API warmed up");
+                });
+            } else {
+                log::info!("[rifthold] warm-up disabled by config");
+            }
+            register_shortcuts(app)?;
+            if config().persist_last_list {
+                load_persisted_list();
+            }
+            #[cfg(target_os = "macos")]
+            if !headless() {
+                macos::start_app_observers(app.handle().clone());
+            }
+            mark_backend_ready(app.handle());
+            Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                if config().persist_last_list {
+                    save_last_list(&app.state::<WindowService>());
+                }
+                #[cfg(target_os = "macos")]
+                macos::stop_app_observers();
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listed(id: &str, app_name: &str) -> WindowInfo {
+        WindowInfo {
+            id: id.into(),
+            title: format!("{app_name} {id}"),
+            app_name: app_name.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nth_listed_follows_grouped_order() {
+        let service = WindowService::new(Arc::new(MockWindowProvider::new()));
+        let windows = vec![listed("1", "Safari"), listed("2", "Safari"), listed("3", "Finder")];
+
+        service.remember_displayed(&group_windows(windows.clone(), GroupMode::Windows));
+        assert_eq!(service.nth_listed(1).as_deref(), Some("2"));
+
+        service.remember_displayed(&group_windows(windows, GroupMode::Apps));
+        assert_eq!(service.nth_listed(0).as_deref(), Some("1"));
+        assert_eq!(service.nth_listed(1).as_deref(), Some("3"));
+        assert_eq!(service.nth_listed(2), None);
+    }
+
+    #[test]
+    fn strip_app_suffix_cases() {
+        let cases = [
+            // (title, app name, expected)
+            ("Inbox - Gmail - Google Chrome", "Google Chrome", Some("Inbox - Gmail")),
+            ("Start Page — Safari", "Safari", Some("Start Page")),
+            ("Release notes – safari", "Safari", Some("Release notes")),
+            ("main.rs — rifthold — Code", "Code", Some("main.rs — rifthold")),
+            // Only the app name: nothing would be left
+            ("Safari", "Safari", None),
+            (" — Safari", "Safari", None),
+            // Suffix naming another app than the owner
+            ("main.rs - rifthold - Visual Studio Code", "Code", None),
+            ("Downloads - Finder", "Google Chrome", None),
+            ("Untitled", "", None),
+        ];
+        for (title, app_name, expected) in cases {
+            assert_eq!(strip_app_suffix(title, app_name).as_deref(), expected, "{title:?} / {app_name:?}");
+        }
+    }
+
+    /// Width and height of an encoded JPEG, read back by decoding it.
+    fn jpeg_dimensions(jpeg: &[u8]) -> (u32, u32) {
+        let decoded = image::load_from_memory_with_format(jpeg, image::ImageFormat::Jpeg).expect("valid JPEG");
+        (decoded.width(), decoded.height())
+    }
+
+    #[test]
+    fn encode_jpeg_pads_to_requested_aspect() {
+        let (width, height) = (120, 40);
+        let rgb: Vec<u8> = (0..width * height).flat_map(|i| [(i % 251) as u8, 90, 180]).collect();
+
+        // Too wide for 16:10: padded vertically
+        let jpeg = encode_jpeg(&rgb, width, height, Some((16, 10)), [0, 0, 0]).unwrap();
+        assert_eq!(jpeg_dimensions(&jpeg), (120, 75));
+
+        // Too wide for 1:1 as well
+        let jpeg = encode_jpeg(&rgb, width, height, Some((1, 1)), [255, 255, 255]).unwrap();
+        assert_eq!(jpeg_dimensions(&jpeg), (120, 120));
+
+        // Too tall for 4:1: padded horizontally
+        let jpeg = encode_jpeg(&rgb, width, height, Some((4, 1)), [0, 0, 0]).unwrap();
+        assert_eq!(jpeg_dimensions(&jpeg), (160, 40));
+
+        // Already 3:1, or no aspect requested: left as captured
+        assert_eq!(jpeg_dimensions(&encode_jpeg(&rgb, width, height, Some((3, 1)), [0, 0, 0]).unwrap()), (120, 40));
+        assert_eq!(jpeg_dimensions(&encode_jpeg(&rgb, width, height, None, [0, 0, 0]).unwrap()), (120, 40));
+    }
+
+    #[test]
+    fn guard_ffi_contains_panics() {
+        fn failing_helper(fail: bool) -> Result<u32, CaptureError> {
+            if fail {
+                panic!("simulated FFI failure");
+            }
+            Ok(7)
+        }
+
+        assert_eq!(guard_ffi("ok", Err(CaptureError::Panicked), || failing_helper(false)), Ok(7));
+        assert_eq!(
+            guard_ffi("failing_helper", Err(CaptureError::Panicked), || failing_helper(true)),
+            Err(CaptureError::Panicked)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn status_with_timeout_kills_overrunning_child() {
+        let started = std::time::Instant::now();
+        let mut command = std::process::Command::new("sleep");
+        command.arg("5");
+        let status = status_with_timeout(&mut command, std::time::Duration::from_millis(50)).unwrap();
+        assert!(status.is_none());
+        // Killed and reaped rather than waited out
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn status_with_timeout_returns_status_of_finished_child() {
+        let mut command = std::process::Command::new("true");
+        let status = status_with_timeout(&mut command, std::time::Duration::from_secs(5)).unwrap();
+        assert!(status.is_some_and(|status| status.success()));
+    }
+
+    #[test]
+    fn truncate_title_counts_chars_of_cjk_titles() {
+        // Three bytes per char, so byte-based cutting would panic or overshoot
+        let max_chars = 12;
+        assert_eq!(truncate_title("漢字かな交じり文のタイ", max_chars), None); // max_chars - 1
+        assert_eq!(truncate_title("漢字かな交じり文のタイト", max_chars), None); // max_chars
+        assert_eq!(
+            truncate_title("漢字かな交じり文のタイトル", max_chars).as_deref(), // max_chars + 1
+            Some("漢字かな交じり文のタイ…")
+        );
+    }
+
+    #[test]
+    fn truncate_title_zero_disables() {
+        assert_eq!(truncate_title("漢字かな交じり文", 0), None);
+    }
 }
 
 #[cfg(target_os = "macos")]
 mod macos {
-    use super::{WindowInfo, WindowProvider};
+    use super::{guard_ffi, stable_window_key, status_with_timeout, CaptureError, CropRect, EncodedThumbnail, WindowBounds, WindowInfo, WindowProvider};
     use core_foundation::{
-        base::{CFTypeRef, TCFType},
+        array::CFArray,
+        base::{CFType, CFTypeRef, TCFType},
+        boolean::CFBoolean,
         dictionary::CFDictionary,
         number::CFNumber,
         string::{CFString, CFStringRef},
     };
     use core_graphics::{
-        display::CGRect,
+        display::{CGDisplay, CGRect},
         geometry::{CGPoint, CGSize},
         window::{
             create_description_from_array, create_window_list, kCGNullWindowID,
-            kCGWindowLayer, kCGWindowListExcludeDesktopElements, kCGWindowListOptionOnScreenOnly,
+            kCGWindowLayer, kCGWindowListExcludeDesktopElements, kCGWindowListOptionAll, kCGWindowListOptionOnScreenOnly,
             kCGWindowName, kCGWindowNumber, kCGWindowOwnerName, kCGWindowOwnerPID,
             kCGWindowImageBoundsIgnoreFraming, kCGWindowImageDefault, kCGWindowListOptionIncludingWindow,
+            kCGWindowImageNominalResolution,
+            kCGWindowIsOnscreen, kCGWindowBounds, kCGWindowAlpha, kCGWindowListOptionOnScreenBelowWindow,
         },
     };
     use cocoa::appkit::{NSApplicationActivateIgnoringOtherApps, NSRunningApplication};
-    use cocoa::base::nil;
-    use std::{collections::HashMap, process::Command, sync::{Arc, Mutex}, time::Instant};
-    use image::ImageEncoder;
-    use base64::{Engine as _, engine::general_purpose};
+    use block::ConcreteBlock;
+    use cocoa::base::{id, nil, BOOL, NO, YES};
+    use objc::{
+        class,
+        declare::ClassDecl,
+        msg_send,
+        runtime::{Class, Object, Sel},
+        sel, sel_impl,
+    };
+    use tauri::Emitter;
+    use std::{
+        collections::{HashMap, HashSet},
+        process::Command,
+        sync::{Arc, LazyLock, Mutex},
+        time::{Duration, Instant},
+    };
     use rayon::prelude::*;
 
     #[derive(Clone)]
@@ -462,29 +4224,456 @@ mod macos {
         title: String,
         is_title_fallback: bool,
         owner_pid: Option<i64>,
+        bundle_id: Option<String>,
+        layer: i64,
+        bounds: Option<WindowBounds>,
+        /// `kCGWindowIsOnscreen`; false for windows on other Spaces, minimized or hidden ones.
+        on_screen: bool,
+        document_path: Option<String>,
+        resources: Option<ProcessResources>,
+        parent_id: Option<String>,
+        degraded: bool,
+    }
+
+    /// Fields read from one CG window description during the first listing pass.
+    struct CgWindow {
+        id: String,
+        app_name: String,
+        cg_title: Option<String>,
+        owner_pid: Option<i64>,
+        layer: i64,
+        bounds: Option<WindowBounds>,
+        on_screen: bool,
+    }
+
+    impl MacWindowEntry {
+        fn stable_key(&self) -> String {
+            let owner = self.bundle_id.as_deref().unwrap_or(&self.app_name);
+            // CG exposes no AX role without a per-window AX round-trip; the window layer is the
+            // cheap stand-in that separates normal windows from panels and overlays.
+            stable_window_key(owner, &format!("layer{}", self.layer), &self.title)
+        }
+
+        fn window_info(self, thumbnail: Option<String>, thumbnail_stale: bool) -> WindowInfo {
+            WindowInfo {
+                stable_key: self.stable_key(),
+                id: self.id,
+                title: self.title,
+                app_name: self.app_name,
+                is_title_fallback: self.is_title_fallback,
+                thumbnail,
+                thumbnail_stale,
+                bounds: self.bounds,
+                document_path: self.document_path,
+                cpu_percent: self.resources.and_then(|r| r.cpu_percent),
+                memory_mb: self.resources.map(|r| r.memory_mb),
+                parent_id: self.parent_id,
+                degraded: self.degraded,
+                ..Default::default()
+            }
+        }
+    }
+
+    /// Turns snapshot entries into `WindowInfo`, capturing thumbnails in parallel when requested.
+    fn window_infos(entries: Vec<MacWindowEntry>, capture_thumbnails: bool) -> Vec<WindowInfo> {
+        if !capture_thumbnails {
+            return entries.into_iter().map(|entry| entry.window_info(None, false)).collect();
+        }
+
+        let thumbnail_start = Instant::now();
+        let max_thumbnail_width = super::adapt_thumbnail_width(entries.len());
+
+        // Use parallel iterator for faster thumbnail capture
+        let capture_all = || -> Vec<WindowInfo> {
+            entries
+                .into_par_iter()
+                .map(|entry| {
+                    let window_id = entry.id.parse::<i64>().unwrap_or(0);
+                    match super::capture_thumbnail(window_id, max_thumbnail_width) {
+                        Ok((thumbnail, stale)) => {
+                            let mut info = entry.window_info(Some(thumbnail.data_url()), stale);
+                            info.corner_glyph = thumbnail.corner_glyph;
+                            info
+                        }
+                        Err(_) => entry.window_info(None, false),
+                    }
+                })
+                .collect()
+        };
+        // On battery with a CPU budget, run on the shared pool no wider than the budget.
+        let results = match super::thumbnail_pool() {
+            Some(pool) => pool.install(capture_all),
+            None => capture_all(),
+        };
+
+        log::debug!(
+            "[rifthold][macos] list_windows completed: windows={} thumbnails_captured={} thumbnail_ms={}",
+            results.len(),
+            results.iter().filter(|w| w.thumbnail.is_some()).count(),
+            thumbnail_start.elapsed().as_millis(),
+        );
+
+        results
+    }
+
+    /// Resolves the bundle identifier of the running application that owns `pid`.
+    fn bundle_id_for_pid(pid: i64) -> Option<String> {
+        unsafe {
+            let app = NSRunningApplication::runningApplicationWithProcessIdentifier(nil, pid as i32);
+            if app == nil {
+                return None;
+            }
+            let bundle_id: id = msg_send![app, bundleIdentifier];
+            nsstring_to_string(bundle_id)
+        }
+    }
+
+    /// One placeholder entry per visible, regular-activation-policy app (`NSWorkspace`).
+    /// Ids are `app-<pid>` so they can never collide with CG window numbers.
+    fn running_app_entries(current_pid: i64) -> Vec<MacWindowEntry> {
+        let mut entries = Vec::new();
+        let self_bundle_id = super::self_bundle_id();
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let apps: id = msg_send![workspace, runningApplications];
+            if apps == nil {
+                return entries;
+            }
+            let count: usize = msg_send![apps, count];
+            for index in 0..count {
+                let app: id = msg_send![apps, objectAtIndex: index];
+                let policy: i64 = msg_send![app, activationPolicy];
+                let hidden: BOOL = msg_send![app, isHidden];
+                let pid: i32 = msg_send![app, processIdentifier];
+                // NSApplicationActivationPolicyRegular == 0
+                if policy != 0 || hidden == YES || pid as i64 == current_pid {
+                    continue;
+                }
+                let name: id = msg_send![app, localizedName];
+                let Some(app_name) = nsstring_to_string(name) else {
+                    continue;
+                };
+                let bundle_id = nsstring_to_string(msg_send![app, bundleIdentifier]);
+                if bundle_id.is_some() && bundle_id == self_bundle_id {
+                    continue;
+                }
+                entries.push(MacWindowEntry {
+                    id: format!("app-{pid}"),
+                    title: app_name.clone(),
+                    app_name,
+                    is_title_fallback: true,
+                    owner_pid: Some(pid as i64),
+                    bundle_id,
+                    layer: 0,
+                    bounds: None,
+                    on_screen: false,
+                    document_path: None,
+                    resources: None,
+                    parent_id: None,
+                    degraded: true,
+                });
+            }
+        }
+        entries
+    }
+
+    // NSBitmapImageFileType.png
+    const BITMAP_IMAGE_FILE_TYPE_PNG: u64 = 4;
+
+    /// The running app's icon (`NSRunningApplication.icon`) encoded as PNG.
+    fn app_icon_png(pid: i64) -> Option<EncodedThumbnail> {
+        unsafe {
+            let app = NSRunningApplication::runningApplicationWithProcessIdentifier(nil, pid as i32);
+            if app == nil {
+                return None;
+            }
+            let icon: id = msg_send![app, icon];
+            if icon == nil {
+                return None;
+            }
+            let tiff: id = msg_send![icon, TIFFRepresentation];
+            if tiff == nil {
+                return None;
+            }
+            let bitmap: id = msg_send![class!(NSBitmapImageRep), imageRepWithData: tiff];
+            if bitmap == nil {
+                return None;
+            }
+            let properties: id = msg_send![class!(NSDictionary), dictionary];
+            let png: id = msg_send![bitmap, representationUsingType: BITMAP_IMAGE_FILE_TYPE_PNG properties: properties];
+            if png == nil {
+                return None;
+            }
+            let length: usize = msg_send![png, length];
+            let bytes: *const u8 = msg_send![png, bytes];
+            if bytes.is_null() || length == 0 {
+                return None;
+            }
+            Some(EncodedThumbnail {
+                bytes: std::slice::from_raw_parts(bytes, length).to_vec(),
+                png: true,
+                phash: None,
+                corner_glyph: None,
+            })
+        }
+    }
+
+    /// Our NSWorkspace notification observer (an instance of `RiftholdAppObserver`, stored as an
+    /// address) and the handle its callbacks emit through.
+    struct AppObserver {
+        observer: usize,
+        app: tauri::AppHandle,
+    }
+
+    static APP_OBSERVER: Mutex<Option<AppObserver>> = Mutex::new(None);
+
+    const DID_LAUNCH_NOTIFICATION: &str = "NSWorkspaceDidLaunchApplicationNotification";
+    const DID_TERMINATE_NOTIFICATION: &str = "NSWorkspaceDidTerminateApplicationNotification";
+    const SCREEN_LOCKED_NOTIFICATION: &str = "com.apple.screenIsLocked";
+    const SCREEN_UNLOCKED_NOTIFICATION: &str = "com.apple.screenIsUnlocked";
+
+    extern "C" fn on_app_launched(_this: &Object, _cmd: Sel, notification: id) {
+        emit_app_notification("app:launched", notification);
+    }
+
+    extern "C" fn on_app_terminated(_this: &Object, _cmd: Sel, notification: id) {
+        emit_app_notification("app:terminated", notification);
+    }
+
+    extern "C" fn on_screen_locked(_this: &Object, _cmd: Sel, _notification: id) {
+        log::info!("[rifthold][macos] screen locked");
+    }
+
+    /// Global hotkeys can stop firing across a lock/unlock cycle, so register them afresh.
+    extern "C" fn on_screen_unlocked(_this: &Object, _cmd: Sel, _notification: id) {
+        log::info!("[rifthold][macos] screen unlocked, re-registering shortcuts");
+        let app = APP_OBSERVER.lock().unwrap().as_ref().map(|observer| observer.app.clone());
+        if let Some(app) = app {
+            match super::reregister_all_shortcuts(&app) {
+                Ok(()) => log::info!("[rifthold][macos] shortcuts re-registered after unlock"),
+                Err(error) => log::warn!("[rifthold][macos] re-registering shortcuts after unlock failed: {}", error),
+            }
+        }
+    }
+
+    /// Emits `event` with the bundle id and name of the app the notification is about.
+    fn emit_app_notification(event: &str, notification: id) {
+        let (bundle_id, name) = unsafe {
+            let user_info: id = msg_send![notification, userInfo];
+            // NSWorkspaceApplicationKey's value is its own name; CFString is toll-free bridged.
+            let key = CFString::new("NSWorkspaceApplicationKey");
+            let app: id = if user_info == nil {
+                nil
+            } else {
+                msg_send![user_info, objectForKey: key.as_concrete_TypeRef() as id]
+            };
+            if app == nil {
+                return;
+            }
+            (
+                nsstring_to_string(msg_send![app, bundleIdentifier]),
+                nsstring_to_string(msg_send![app, localizedName]),
+            )
+        };
+        if let Some(observer) = APP_OBSERVER.lock().unwrap().as_ref() {
+            let _ = observer
+                .app
+                .emit(event, serde_json::json!({ "bundleId": bundle_id, "name": name }));
+        }
+    }
+
+    fn observer_class() -> &'static Class {
+        static REGISTER: std::sync::Once = std::sync::Once::new();
+        REGISTER.call_once(|| {
+            let mut decl = ClassDecl::new("RiftholdAppObserver", class!(NSObject))
+                .expect("RiftholdAppObserver already registered");
+            unsafe {
+                decl.add_method(sel!(appLaunched:), on_app_launched as extern "C" fn(&Object, Sel, id));
+                decl.add_method(sel!(appTerminated:), on_app_terminated as extern "C" fn(&Object, Sel, id));
+                decl.add_method(sel!(screenLocked:), on_screen_locked as extern "C" fn(&Object, Sel, id));
+                decl.add_method(sel!(screenUnlocked:), on_screen_unlocked as extern "C" fn(&Object, Sel, id));
+            }
+            decl.register();
+        });
+        Class::get("RiftholdAppObserver").expect("RiftholdAppObserver registered")
+    }
+
+    /// Emits `app:launched` / `app:terminated` from NSWorkspace notifications, and re-registers
+    /// the shortcuts on screen unlock, until `stop_app_observers`.
+    pub fn start_app_observers(app: tauri::AppHandle) {
+        let mut state = APP_OBSERVER.lock().unwrap();
+        if state.is_some() {
+            return;
+        }
+        unsafe {
+            let observer: id = msg_send![observer_class(), new];
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let center: id = msg_send![workspace, notificationCenter];
+            for (selector, name) in [
+                (sel!(appLaunched:), DID_LAUNCH_NOTIFICATION),
+                (sel!(appTerminated:), DID_TERMINATE_NOTIFICATION),
+            ] {
+                let name = CFString::new(name);
+                let _: () = msg_send![center, addObserver: observer
+                    selector: selector
+                    name: name.as_concrete_TypeRef() as id
+                    object: nil];
+            }
+            // Lock state is only announced on the distributed center.
+            let distributed: id = msg_send![class!(NSDistributedNotificationCenter), defaultCenter];
+            for (selector, name) in [
+                (sel!(screenLocked:), SCREEN_LOCKED_NOTIFICATION),
+                (sel!(screenUnlocked:), SCREEN_UNLOCKED_NOTIFICATION),
+            ] {
+                let name = CFString::new(name);
+                let _: () = msg_send![distributed, addObserver: observer
+                    selector: selector
+                    name: name.as_concrete_TypeRef() as id
+                    object: nil];
+            }
+            *state = Some(AppObserver { observer: observer as usize, app });
+        }
+    }
+
+    /// Unregisters and releases the observer so no callback outlives the app handle.
+    pub fn stop_app_observers() {
+        let Some(state) = APP_OBSERVER.lock().unwrap().take() else {
+            return;
+        };
+        unsafe {
+            let observer = state.observer as id;
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let center: id = msg_send![workspace, notificationCenter];
+            let _: () = msg_send![center, removeObserver: observer];
+            let distributed: id = msg_send![class!(NSDistributedNotificationCenter), defaultCenter];
+            let _: () = msg_send![distributed, removeObserver: observer];
+            let _: () = msg_send![observer, release];
+        }
+    }
+
+    /// Pid of the first running process of `bundle_id`.
+    fn pid_for_bundle(bundle_id: &str) -> Option<i32> {
+        let bundle_id = CFString::new(bundle_id);
+        unsafe {
+            let apps: id = msg_send![
+                class!(NSRunningApplication),
+                runningApplicationsWithBundleIdentifier: bundle_id.as_concrete_TypeRef() as id
+            ];
+            if apps == nil {
+                return None;
+            }
+            let count: usize = msg_send![apps, count];
+            if count == 0 {
+                return None;
+            }
+            let app: id = msg_send![apps, objectAtIndex: 0usize];
+            let pid: i32 = msg_send![app, processIdentifier];
+            Some(pid)
+        }
+    }
+
+    /// Whether any process of `bundle_id` is running.
+    fn is_bundle_running(bundle_id: &str) -> bool {
+        let bundle_id = CFString::new(bundle_id);
+        unsafe {
+            let apps: id = msg_send![
+                class!(NSRunningApplication),
+                runningApplicationsWithBundleIdentifier: bundle_id.as_concrete_TypeRef() as id
+            ];
+            if apps == nil {
+                return false;
+            }
+            let count: usize = msg_send![apps, count];
+            count > 0
+        }
+    }
+
+    /// Bundle id of the running app; `None` for an unbundled binary (e.g. `tauri dev`).
+    pub fn main_bundle_id() -> Option<String> {
+        unsafe {
+            let bundle: id = msg_send![class!(NSBundle), mainBundle];
+            if bundle == nil {
+                return None;
+            }
+            nsstring_to_string(msg_send![bundle, bundleIdentifier])
+        }
+    }
+
+    unsafe fn nsstring_to_string(value: id) -> Option<String> {
+        if value == nil {
+            return None;
+        }
+        let utf8: *const std::os::raw::c_char = msg_send![value, UTF8String];
+        if utf8.is_null() {
+            return None;
+        }
+        Some(std::ffi::CStr::from_ptr(utf8).to_string_lossy().into_owned())
+    }
+
+    /// Entries from the last enumeration, in CG (front-to-back) order, plus an id index.
+    #[derive(Default)]
+    struct Snapshot {
+        entries: Vec<MacWindowEntry>,
+        by_id: HashMap<String, usize>,
+        search_index: super::search::SearchIndex,
     }
 
     pub struct MacWindowProvider {
-        snapshot: Arc<Mutex<HashMap<String, MacWindowEntry>>>,
+        snapshot: Arc<Mutex<Snapshot>>,
     }
 
     impl MacWindowProvider {
         pub fn new() -> Self {
             Self {
-                snapshot: Arc::new(Mutex::new(HashMap::new())),
+                snapshot: Arc::new(Mutex::new(Snapshot::default())),
             }
         }
 
         fn refresh_snapshot(&self, entries: &[MacWindowEntry]) {
             let mut snapshot = self.snapshot.lock().unwrap();
-            snapshot.clear();
-            for entry in entries {
-                snapshot.insert(entry.id.clone(), entry.clone());
-            }
+            snapshot.entries = entries.to_vec();
+            snapshot.by_id = entries
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| (entry.id.clone(), index))
+                .collect();
+            let rebuilt = snapshot.search_index.update(
+                entries
+                    .iter()
+                    .map(|entry| (entry.id.as_str(), entry.title.as_str(), entry.app_name.as_str())),
+            );
+            log::debug!("[rifthold][macos] search index: {} of {} entries rebuilt", rebuilt, entries.len());
         }
 
         fn find_entry(&self, id: &str) -> Option<MacWindowEntry> {
-            self.snapshot.lock().unwrap().get(id).cloned()
+            let snapshot = self.snapshot.lock().unwrap();
+            snapshot
+                .by_id
+                .get(id)
+                .and_then(|&index| snapshot.entries.get(index))
+                .cloned()
+        }
+
+        /// Looks `id` up in the cached snapshot, refreshing once if it is missing.
+        fn resolve_entry(&self, id: &str) -> Result<MacWindowEntry, String> {
+            self.find_entry(id)
+                .or_else(|| {
+                    let _ = self.list(false); // Don't need thumbnails for activation
+                    self.find_entry(id)
+                })
+                .ok_or_else(|| format!("window id {id} not found"))
+        }
+
+        /// Degraded listing for when CG window enumeration fails: one entry per visible regular
+        /// app, so the user can at least switch apps. These entries never carry thumbnails.
+        fn list_degraded(&self, current_pid: i64) -> Vec<WindowInfo> {
+            let entries = running_app_entries(current_pid);
+            self.refresh_snapshot(&entries);
+            entries.into_iter().map(|entry| entry.window_info(None, false)).collect()
+        }
+
+        fn snapshot_entries(&self) -> Vec<MacWindowEntry> {
+            self.snapshot.lock().unwrap().entries.clone()
         }
 
         fn clear_title_cache(&self) {
@@ -495,54 +4684,121 @@ mod macos {
 
     fn string_for_key(dict: &CFDictionary<CFString, core_foundation::base::CFType>, key: CFStringRef) -> Option<String> {
         let key = unsafe { CFString::wrap_under_get_rule(key) };
-        dict.find(&key).and_then(|value| {
-            let cf_type = value.clone();
-            cf_type
-                .downcast::<CFString>()
-                .map(|s| s.to_string())
-                .filter(|s| !s.trim().is_empty())
+        dict.find(&key).and_then(|value| {
+            let cf_type = value.clone();
+            cf_type
+                .downcast::<CFString>()
+                .map(|s| s.to_string())
+                .filter(|s| !s.trim().is_empty())
+        })
+    }
+
+    fn number_for_key(
+        dict: &CFDictionary<CFString, core_foundation::base::CFType>,
+        key: CFStringRef,
+    ) -> Option<i64> {
+        let key = unsafe { CFString::wrap_under_get_rule(key) };
+        dict.find(&key)
+            .and_then(|value| value.clone().downcast::<CFNumber>())
+            .and_then(|number| number.to_i64())
+    }
+
+    fn float_for_key(
+        dict: &CFDictionary<CFString, core_foundation::base::CFType>,
+        key: CFStringRef,
+    ) -> Option<f64> {
+        let key = unsafe { CFString::wrap_under_get_rule(key) };
+        dict.find(&key)
+            .and_then(|value| value.clone().downcast::<CFNumber>())
+            .and_then(|number| number.to_f64())
+    }
+
+    fn bounds_for_key(
+        dict: &CFDictionary<CFString, core_foundation::base::CFType>,
+        key: CFStringRef,
+    ) -> Option<WindowBounds> {
+        let key = unsafe { CFString::wrap_under_get_rule(key) };
+        let value = dict.find(&key)?;
+        let mut rect = CGRect::new(&CGPoint::new(0.0, 0.0), &CGSize::new(0.0, 0.0));
+        let ok = unsafe { CGRectMakeWithDictionaryRepresentation(value.as_CFTypeRef(), &mut rect) };
+        ok.then(|| WindowBounds {
+            x: rect.origin.x,
+            y: rect.origin.y,
+            width: rect.size.width,
+            height: rect.size.height,
         })
     }
 
-    fn number_for_key(
+    fn bool_for_key(
         dict: &CFDictionary<CFString, core_foundation::base::CFType>,
         key: CFStringRef,
-    ) -> Option<i64> {
+    ) -> Option<bool> {
         let key = unsafe { CFString::wrap_under_get_rule(key) };
         dict.find(&key)
-            .and_then(|value| value.clone().downcast::<CFNumber>())
-            .and_then(|number| number.to_i64())
+            .and_then(|value| value.clone().downcast::<CFBoolean>())
+            .map(bool::from)
+    }
+
+    /// When each app was last brought forward by `activate_app`, for `Config::activation_dedup_ms`.
+    static RECENT_APP_ACTIVATIONS: LazyLock<Mutex<HashMap<String, Instant>>> =
+        LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    /// Whether `app_name` may be handed to `open -a` / System Events. Not our placeholder
+    /// name: `open -a "App"` would target whatever app happens to be called that.
+    fn activates_by_name(app_name: &str) -> bool {
+        !app_name.is_empty() && app_name != PLACEHOLDER_APP_NAME
     }
 
     fn activate_app(app_name: &str) -> Result<(), String> {
-        if app_name.is_empty() {
-            return Err("missing app name for activation".into());
+        if !activates_by_name(app_name) {
+            return Err(format!("no usable app name for activation ({app_name:?})"));
+        }
+
+        let dedup = Duration::from_millis(super::config().activation_dedup_ms);
+        {
+            let mut recent = RECENT_APP_ACTIVATIONS.lock().unwrap();
+            let now = Instant::now();
+            recent.retain(|_, at| now.duration_since(*at) < dedup);
+            if recent.contains_key(app_name) {
+                log::debug!("[rifthold] {} activated within {:?}; skipping open/osascript", app_name, dedup);
+                return Ok(());
+            }
         }
 
+        let timeout = Duration::from_millis(super::config().open_timeout_ms);
+
         // Prefer LaunchServices activation to avoid per-app automation prompts.
-        let open_status = Command::new("open")
-            .arg("-a")
-            .arg(app_name)
-            .status()
-            .map_err(|error| format!("activation failed: {error}"))?;
+        let mut open = Command::new("open");
+        open.arg("-a").arg(app_name);
+        let Some(open_status) = status_with_timeout(&mut open, timeout).map_err(|error| format!("activation failed: {error}"))? else {
+            log::warn!("[rifthold] `open -a {}` timed out after {:?}; falling back to AX raise", app_name, timeout);
+            return Ok(());
+        };
 
         // Ensure the app is frontmost even if `open` cannot resolve the name; this uses
         // System Events (Accessibility) instead of per-app automation prompts.
-        let _ = Command::new("osascript")
-            .arg("-e")
-            .arg(format!(
-                r#"tell application "System Events" to if exists process "{}" then set frontmost of process "{}" to true"#,
-                app_name, app_name
-            ))
-            .status();
+        let mut osascript = Command::new("osascript");
+        osascript.arg("-e").arg(format!(
+            r#"tell application "System Events" to if exists process "{}" then set frontmost of process "{}" to true"#,
+            app_name, app_name
+        ));
+        if let Ok(None) = status_with_timeout(&mut osascript, timeout) {
+            log::warn!("[rifthold] osascript activation of {} timed out after {:?}", app_name, timeout);
+        }
 
         if open_status.success() {
+            if !dedup.is_zero() {
+                RECENT_APP_ACTIVATIONS.lock().unwrap().insert(app_name.to_string(), Instant::now());
+            }
             Ok(())
         } else {
             Err(format!("open -a returned status {open_status:?}"))
         }
     }
 
+    /// App name given to windows whose owner has none.
+    const PLACEHOLDER_APP_NAME: &str = "App";
+
     type AXUIElementRef = *const std::ffi::c_void;
     type AXError = i32;
     type CGImageRef = *const std::ffi::c_void;
@@ -550,6 +4806,10 @@ mod macos {
 
     #[allow(non_upper_case_globals)]
     const kAXErrorSuccess: AXError = 0;
+    #[allow(non_upper_case_globals)]
+    const kAXValueCGPointType: u32 = 1;
+    #[allow(non_upper_case_globals)]
+    const kAXValueCGSizeType: u32 = 2;
 
     // CGRectNull is used to indicate that the system should determine the bounds automatically
     fn cg_rect_null() -> CGRect {
@@ -571,14 +4831,24 @@ mod macos {
             element: AXUIElementRef,
             action: CFStringRef,
         ) -> AXError;
+        fn AXUIElementSetAttributeValue(
+            element: AXUIElementRef,
+            attribute: CFStringRef,
+            value: CFTypeRef,
+        ) -> AXError;
+        fn AXValueGetValue(value: CFTypeRef, value_type: u32, value_ptr: *mut std::ffi::c_void) -> bool;
+        fn AXValueCreate(value_type: u32, value_ptr: *const std::ffi::c_void) -> CFTypeRef;
         fn CFRelease(cf: CFTypeRef);
-        fn CFArrayGetCount(array: CFTypeRef) -> isize;
-        fn CFArrayGetValueAtIndex(array: CFTypeRef, idx: isize) -> *const std::ffi::c_void;
     }
 
     #[link(name = "CoreGraphics", kind = "framework")]
     extern "C" {
         fn CGPreflightScreenCaptureAccess() -> bool;
+        fn CGDisplayIsAsleep(display: u32) -> u32;
+        fn CGWarpMouseCursorPosition(new_cursor_position: CGPoint) -> i32;
+        fn CGAssociateMouseAndMouseCursorPosition(connected: u32) -> i32;
+        fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: CGWindowID) -> CFTypeRef;
+        fn CGRectMakeWithDictionaryRepresentation(dict: CFTypeRef, rect: *mut CGRect) -> bool;
         fn CGWindowListCreateImage(
             screen_bounds: CGRect,
             list_option: u32,
@@ -586,6 +4856,7 @@ mod macos {
             image_option: u32,
         ) -> CGImageRef;
         fn CGImageGetWidth(image: CGImageRef) -> usize;
+        fn CGImageCreateWithImageInRect(image: CGImageRef, rect: CGRect) -> CGImageRef;
         fn CGImageGetHeight(image: CGImageRef) -> usize;
         fn CGImageGetDataProvider(image: CGImageRef) -> *const std::ffi::c_void;
         fn CGDataProviderCopyData(provider: *const std::ffi::c_void) -> CFTypeRef;
@@ -593,10 +4864,15 @@ mod macos {
         fn CFDataGetLength(data: CFTypeRef) -> isize;
         fn CGImageGetBytesPerRow(image: CGImageRef) -> usize;
         fn CGImageRelease(image: CGImageRef);
+        fn CGImageRetain(image: CGImageRef) -> CGImageRef;
 
         // CGContext functions for hardware-accelerated scaling
         fn CGColorSpaceCreateDeviceRGB() -> *const std::ffi::c_void;
+        fn CGColorSpaceCreateWithName(name: CFStringRef) -> *const std::ffi::c_void;
+        fn CGColorSpaceRetain(color_space: *const std::ffi::c_void) -> *const std::ffi::c_void;
         fn CGColorSpaceRelease(color_space: *const std::ffi::c_void);
+        fn CGImageGetColorSpace(image: CGImageRef) -> *const std::ffi::c_void;
+        static kCGColorSpaceSRGB: CFStringRef;
         fn CGBitmapContextCreate(
             data: *mut std::ffi::c_void,
             width: usize,
@@ -618,217 +4894,1239 @@ mod macos {
     #[allow(non_upper_case_globals)]
     const kCGBitmapByteOrder32Big: u32 = 4 << 12;
 
-    // CGInterpolationQuality constants
-    #[allow(non_upper_case_globals)]
-    const kCGInterpolationHigh: i32 = 3;
+    // CGInterpolationQuality constants
+    #[allow(non_upper_case_globals)]
+    const kCGInterpolationLow: i32 = 2;
+    #[allow(non_upper_case_globals)]
+    const kCGInterpolationHigh: i32 = 3;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPSCopyPowerSourcesInfo() -> CFTypeRef;
+        fn IOPSGetProvidingPowerSourceType(snapshot: CFTypeRef) -> CFStringRef;
+    }
+
+    /// Moves the cursor to a point in global display coordinates (top-left origin, the same
+    /// space as `kCGWindowBounds`). Re-associating the mouse avoids the post-warp freeze.
+    pub fn warp_cursor(x: f64, y: f64) {
+        unsafe {
+            CGWarpMouseCursorPosition(CGPoint::new(x, y));
+            CGAssociateMouseAndMouseCursorPosition(1);
+        }
+    }
+
+    /// Active displays with their CG frames (global, top-left origin).
+    pub fn displays() -> Vec<super::DisplayInfo> {
+        let main_id = CGDisplay::main().id;
+        CGDisplay::active_displays()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|display_id| {
+                let frame = CGDisplay::new(display_id).bounds();
+                super::DisplayInfo {
+                    id: display_id,
+                    bounds: WindowBounds {
+                        x: frame.origin.x,
+                        y: frame.origin.y,
+                        width: frame.size.width,
+                        height: frame.size.height,
+                    },
+                    is_main: display_id == main_id,
+                }
+            })
+            .collect()
+    }
+
+    /// Which power source is currently providing power, per IOKit.
+    pub fn power_state() -> super::PowerState {
+        unsafe {
+            let snapshot = IOPSCopyPowerSourcesInfo();
+            if snapshot.is_null() {
+                return super::PowerState::Unknown;
+            }
+            // The type string is owned by the snapshot ("Get" rule).
+            let source = IOPSGetProvidingPowerSourceType(snapshot);
+            let state = if source.is_null() {
+                super::PowerState::Unknown
+            } else {
+                match CFString::wrap_under_get_rule(source).to_string().as_str() {
+                    "AC Power" => super::PowerState::Ac,
+                    "Battery Power" => super::PowerState::Battery,
+                    _ => super::PowerState::Unknown,
+                }
+            };
+            CFRelease(snapshot);
+            state
+        }
+    }
+
+    /// `ns_window`'s window server number, which is its CGWindowID.
+    pub fn window_number(ns_window: id) -> i64 {
+        let number: isize = unsafe { msg_send![ns_window, windowNumber] };
+        number as i64
+    }
+
+    pub fn set_window_level(ns_window: id, level: i64) {
+        unsafe {
+            let _: () = msg_send![ns_window, setLevel: level];
+        }
+    }
+
+    pub fn set_window_alpha(ns_window: id, alpha: f64) {
+        unsafe {
+            let _: () = msg_send![ns_window, setAlphaValue: alpha];
+        }
+    }
+
+    /// Animates `ns_window`'s alpha to 1 through its animator proxy.
+    pub fn fade_in_window(ns_window: id, duration_ms: u64) {
+        unsafe {
+            let _: () = msg_send![class!(NSAnimationContext), beginGrouping];
+            let context: id = msg_send![class!(NSAnimationContext), currentContext];
+            let _: () = msg_send![context, setDuration: duration_ms as f64 / 1000.0];
+            let animator: id = msg_send![ns_window, animator];
+            let _: () = msg_send![animator, setAlphaValue: 1.0f64];
+            let _: () = msg_send![class!(NSAnimationContext), endGrouping];
+        }
+    }
+
+    pub fn has_screen_recording_permission() -> bool {
+        unsafe { CGPreflightScreenCaptureAccess() }
+    }
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        fn TISCopyInputSourceForLanguage(language: CFStringRef) -> CFTypeRef;
+        fn TISSelectInputSource(input_source: CFTypeRef) -> i32;
+    }
+
+    pub fn switch_to_english_input() {
+        unsafe {
+            let lang = CFString::new("en");
+            let source = TISCopyInputSourceForLanguage(lang.as_concrete_TypeRef());
+            if !source.is_null() {
+                TISSelectInputSource(source);
+                CFRelease(source);
+            }
+        }
+    }
+
+    pub fn capture_window_thumbnail(window_id: i64, max_width: u32) -> Option<String> {
+        capture_window_thumbnail_cropped(window_id, max_width, None)
+    }
+
+    /// Captures the window and, when `crop` is given, scales only that sub-rect (in image
+    /// pixels, clamped to the captured bounds) into the thumbnail.
+    pub fn capture_window_thumbnail_cropped(
+        window_id: i64,
+        max_width: u32,
+        crop: Option<CropRect>,
+    ) -> Option<String> {
+        try_capture_window_thumbnail(window_id, max_width, crop, None)
+            .ok()
+            .map(|thumbnail| thumbnail.data_url())
+    }
+
+    /// Creates (+1) the `crop` sub-rect of `cg_image`, clamped to its bounds; `None` when the
+    /// crop lies outside the image or CG fails.
+    unsafe fn create_cropped_image(cg_image: CGImageRef, crop: CropRect) -> Option<CGImageRef> {
+        let rect = crop.clamped(CGImageGetWidth(cg_image) as f64, CGImageGetHeight(cg_image) as f64)?;
+        let cropped = CGImageCreateWithImageInRect(
+            cg_image,
+            CGRect::new(&CGPoint::new(rect.x, rect.y), &CGSize::new(rect.width, rect.height)),
+        );
+        (!cropped.is_null()).then_some(cropped)
+    }
+
+    /// `capture_window_thumbnail_cropped` with the reason of a failure, which is also logged.
+    /// With `glyph_crop`, the corner glyph is cropped from the same capture.
+    fn try_capture_window_thumbnail(
+        window_id: i64,
+        max_width: u32,
+        crop: Option<CropRect>,
+        glyph_crop: Option<CropRect>,
+    ) -> Result<EncodedThumbnail, CaptureError> {
+        let captured = guard_ffi("capture_window_thumbnail", Err(CaptureError::Panicked), || {
+            let start = Instant::now();
+
+            unsafe {
+                let mut cg_image = create_window_image(window_id)?;
+                let corner_glyph = glyph_crop.and_then(|glyph_crop| {
+                    let glyph_image = create_cropped_image(cg_image, glyph_crop)?;
+                    let glyph = encode_scaled_image(glyph_image, super::CORNER_GLYPH_WIDTH);
+                    CGImageRelease(glyph_image);
+                    glyph.ok().map(|glyph| glyph.data_url())
+                });
+                if let Some(crop) = crop {
+                    match create_cropped_image(cg_image, crop) {
+                        Some(cropped) => {
+                            CGImageRelease(cg_image);
+                            cg_image = cropped;
+                        }
+                        None => log::debug!(
+                            "[thumbnail] window_id={} crop {:?} outside {}x{}, using full image",
+                            window_id,
+                            crop,
+                            CGImageGetWidth(cg_image),
+                            CGImageGetHeight(cg_image)
+                        ),
+                    }
+                }
+
+                let thumbnail = encode_scaled_image(cg_image, max_width).map(|mut thumbnail| {
+                    thumbnail.corner_glyph = corner_glyph;
+                    thumbnail
+                });
+                CGImageRelease(cg_image);
+
+                let elapsed = start.elapsed().as_millis();
+                if elapsed > 50 {
+                    log::debug!("[thumbnail] window_id={} {}ms", window_id, elapsed);
+                }
+
+                thumbnail
+            }
+        });
+        if let Err(reason) = captured {
+            log::debug!("[thumbnail] window_id={} capture failed: {}", window_id, reason);
+        }
+        captured
+    }
+
+    /// `try_capture_window_thumbnail` through `create_offscreen_window_image`.
+    fn capture_offscreen_thumbnail(window_id: i64, max_width: u32) -> Result<EncodedThumbnail, CaptureError> {
+        guard_ffi("capture_offscreen_thumbnail", Err(CaptureError::Panicked), || unsafe {
+            let cg_image = create_offscreen_window_image(window_id)?;
+            let thumbnail = encode_scaled_image(cg_image, max_width);
+            CGImageRelease(cg_image);
+            thumbnail
+        })
+    }
+
+    /// Frame of the Dock tile (`AXMinimizedWindowDockItem`) of the minimized window `title`.
+    fn dock_miniature_frame(title: &str) -> Option<WindowBounds> {
+        let dock = OwnedAXElement::application(pid_for_bundle("com.apple.dock")?)?;
+        unsafe {
+            let lists = ax_array_attribute(dock.as_raw(), "AXChildren")?;
+            lists.iter().find_map(|list| {
+                let items = ax_array_attribute(list.as_CFTypeRef() as AXUIElementRef, "AXChildren")?;
+                items.iter().find_map(|item| {
+                    let item_ref = item.as_CFTypeRef() as AXUIElementRef;
+                    let is_miniature = ax_string_attribute(item_ref, "AXSubrole").as_deref()
+                        == Some("AXMinimizedWindowDockItem");
+                    (is_miniature && ax_string_attribute(item_ref, "AXTitle").as_deref() == Some(title))
+                        .then(|| ax_frame(item_ref))
+                        .flatten()
+                })
+            })
+        }
+    }
+
+    /// Captures the on-screen Dock tile of minimized window `window_id`. Fails when the window
+    /// has no tile or the Dock is hidden (the tile is then off screen or zero-sized).
+    fn capture_dock_miniature(window_id: i64, max_width: u32) -> Result<EncodedThumbnail, CaptureError> {
+        let title = window_description(window_id)
+            .and_then(|dict| string_for_key(&dict, unsafe { kCGWindowName }))
+            .ok_or(CaptureError::WindowNotFound)?;
+        let frame = dock_miniature_frame(&title).ok_or(CaptureError::WindowNotFound)?;
+        guard_ffi("capture_dock_miniature", Err(CaptureError::Panicked), || unsafe {
+            let cg_image = CGWindowListCreateImage(
+                CGRect::new(&CGPoint::new(frame.x, frame.y), &CGSize::new(frame.width, frame.height)),
+                kCGWindowListOptionOnScreenOnly,
+                kCGNullWindowID,
+                kCGWindowImageDefault,
+            );
+            if cg_image.is_null() {
+                return Err(CaptureError::NullImage);
+            }
+            if CGImageGetWidth(cg_image) == 0 || CGImageGetHeight(cg_image) == 0 {
+                CGImageRelease(cg_image);
+                return Err(CaptureError::ZeroSize);
+            }
+            let thumbnail = encode_scaled_image(cg_image, max_width);
+            CGImageRelease(cg_image);
+            thumbnail
+        })
+    }
+
+    /// Last successful capture per window, served when a fresh capture comes back empty.
+    static LAST_GOOD_THUMBNAILS: LazyLock<Mutex<HashMap<i64, EncodedThumbnail>>> =
+        LazyLock::new(|| Mutex::new(HashMap::new()));
+
+    /// Captures a thumbnail, falling back to an off-screen CG capture and then the last good
+    /// capture for windows that are not on screen (most often because they live on another
+    /// Space, which CG rarely captures). The returned flag is true when the image may be stale.
+    pub fn capture_window_thumbnail_or_cached(
+        window_id: i64,
+        max_width: u32,
+    ) -> Result<(EncodedThumbnail, bool), CaptureError> {
+        // A sleeping display captures as solid black; keep the last good image instead.
+        if is_on_sleeping_display(window_id) {
+            let cached = LAST_GOOD_THUMBNAILS.lock().unwrap().get(&window_id).cloned();
+            log::debug!(
+                "[thumbnail] window_id={} on a sleeping display, cached={}",
+                window_id,
+                cached.is_some()
+            );
+            return cached.map(|thumbnail| (thumbnail, true)).ok_or(CaptureError::DisplayAsleep);
+        }
+
+        let strategy = capture_strategy(window_id);
+        if strategy.0 == super::CaptureStrategy::Skip {
+            return strategy
+                .1
+                .and_then(app_icon_png)
+                .map(|icon| (icon, false))
+                .ok_or(CaptureError::Skipped);
+        }
+
+        // AX exposes no image for the Dock's miniature of a minimized window, only the frame of
+        // its Dock tile, so the tile is captured from the screen. With the Dock hidden, the last
+        // capture from before the window was minimized stands in for it.
+        if is_window_on_screen(window_id) == Some(false) && is_window_minimized(window_id) == Some(true) {
+            if let Ok(thumbnail) = capture_dock_miniature(window_id, max_width) {
+                log::debug!("[thumbnail] window_id={} minimized, captured its Dock tile", window_id);
+                return Ok((thumbnail, true));
+            }
+            if let Some(cached) = LAST_GOOD_THUMBNAILS.lock().unwrap().get(&window_id).cloned() {
+                log::debug!("[thumbnail] window_id={} minimized, serving cached thumbnail", window_id);
+                return Ok((cached, true));
+            }
+            return try_capture_window_thumbnail(window_id, max_width, None, super::corner_glyph_crop())
+                .map(|thumbnail| (thumbnail, true));
+        }
+
+        let error = match try_capture_window_thumbnail(window_id, max_width, None, super::corner_glyph_crop()) {
+            Ok(thumbnail) => {
+                super::record_phash(&window_id.to_string(), thumbnail.phash);
+                LAST_GOOD_THUMBNAILS
+                    .lock()
+                    .unwrap()
+                    .insert(window_id, thumbnail.clone());
+                return Ok((thumbnail, false));
+            }
+            Err(error) => error,
+        };
+
+        if is_window_on_screen(window_id) != Some(false) {
+            return Err(error);
+        }
+
+        // Best effort for windows on other Spaces: a plain CG capture that does not restrict
+        // itself to on-screen windows. Usually empty, so the result is marked stale either way.
+        if let Ok(thumbnail) = capture_offscreen_thumbnail(window_id, max_width) {
+            log::debug!("[thumbnail] window_id={} off-screen, captured with kCGWindowListOptionAll", window_id);
+            super::record_phash(&window_id.to_string(), thumbnail.phash);
+            LAST_GOOD_THUMBNAILS
+                .lock()
+                .unwrap()
+                .insert(window_id, thumbnail.clone());
+            return Ok((thumbnail, true));
+        }
+
+        let cached = LAST_GOOD_THUMBNAILS
+            .lock()
+            .unwrap()
+            .get(&window_id)
+            .cloned()
+            .ok_or(CaptureError::OffScreen)?;
+        log::debug!("[thumbnail] window_id={} off-screen, serving cached thumbnail", window_id);
+        Ok((cached, true))
+    }
+
+    fn prune_thumbnail_cache(entries: &[MacWindowEntry]) {
+        let live: HashSet<i64> = entries
+            .iter()
+            .filter_map(|entry| entry.id.parse::<i64>().ok())
+            .collect();
+        LAST_GOOD_THUMBNAILS
+            .lock()
+            .unwrap()
+            .retain(|id, _| live.contains(id));
+    }
+
+    /// Reads the description of a single window, regardless of which Space it is on.
+    fn window_description(window_id: i64) -> Option<CFDictionary<CFString, core_foundation::base::CFType>> {
+        unsafe {
+            let array_ref = CGWindowListCopyWindowInfo(kCGWindowListOptionIncludingWindow, window_id as CGWindowID);
+            if array_ref.is_null() {
+                return None;
+            }
+            let descriptions: CFArray<CFDictionary<CFString, core_foundation::base::CFType>> =
+                CFArray::wrap_under_create_rule(array_ref as _);
+            let first = descriptions.iter().next().map(|dict| (*dict).clone());
+            first
+        }
+    }
+
+    /// The window's frame right now. The snapshot frame goes stale when the window is dragged
+    /// to another display (or its display is unplugged) after listing; moves are logged.
+    fn live_window_bounds(entry: &MacWindowEntry) -> Option<WindowBounds> {
+        let live = entry
+            .id
+            .parse()
+            .ok()
+            .and_then(window_description)
+            .and_then(|dict| bounds_for_key(&dict, unsafe { kCGWindowBounds }));
+        match (entry.bounds, live) {
+            (Some(listed), Some(live)) if !listed.approx_eq(&live, 2.0) => log::info!(
+                "[rifthold][macos] window {} moved since listing: {:?} -> {:?}",
+                entry.id,
+                listed,
+                live
+            ),
+            (Some(_), None) => log::debug!("[rifthold][macos] window {} live frame unavailable, using the listed one", entry.id),
+            _ => {}
+        }
+        live.or(entry.bounds)
+    }
+
+    /// Whether the display holding most of the window is asleep (`CGDisplayIsAsleep`).
+    fn is_on_sleeping_display(window_id: i64) -> bool {
+        let Some(bounds) = window_description(window_id).and_then(|dict| bounds_for_key(&dict, unsafe { kCGWindowBounds })) else {
+            return false;
+        };
+        super::display_for_bounds(&bounds, &displays())
+            .is_some_and(|display_id| unsafe { CGDisplayIsAsleep(display_id) != 0 })
+    }
+
+    fn is_window_on_screen(window_id: i64) -> Option<bool> {
+        let dict = window_description(window_id)?;
+        Some(bool_for_key(&dict, unsafe { kCGWindowIsOnscreen }).unwrap_or(false))
+    }
+
+    /// Whether window `window_id` is minimized into the Dock, from its AX `AXMinimized`. `None`
+    /// when the window or its AX element cannot be found.
+    fn is_window_minimized(window_id: i64) -> Option<bool> {
+        let dict = window_description(window_id)?;
+        let pid = number_for_key(&dict, unsafe { kCGWindowOwnerPID })?;
+        let title = string_for_key(&dict, unsafe { kCGWindowName }).unwrap_or_default();
+        let bounds = bounds_for_key(&dict, unsafe { kCGWindowBounds });
+        unsafe {
+            let window = find_ax_window(pid as i32, bounds, &title).ok()?;
+            ax_attribute(window.as_CFTypeRef() as AXUIElementRef, "AXMinimized")?
+                .downcast::<CFBoolean>()
+                .map(bool::from)
+        }
+    }
+
+    /// Captures the window once and scales the same source image to every requested width,
+    /// so a small list tile and a larger preview cost a single CG capture.
+    pub fn capture_window_thumbnail_sizes(window_id: i64, widths: &[u32]) -> HashMap<u32, String> {
+        let start = Instant::now();
+        let mut results = HashMap::with_capacity(widths.len());
+
+        unsafe {
+            let Ok(cg_image) = create_window_image(window_id) else {
+                return results;
+            };
+
+            for &width in widths {
+                if results.contains_key(&width) {
+                    continue;
+                }
+                if let Ok(thumbnail) = encode_scaled_image(cg_image, width) {
+                    results.insert(width, thumbnail.data_url());
+                }
+            }
+            CGImageRelease(cg_image);
+        }
+
+        let elapsed = start.elapsed().as_millis();
+        if elapsed > 50 {
+            log::debug!("[thumbnail] window_id={} sizes={:?} {}ms", window_id, widths, elapsed);
+        }
+
+        results
+    }
+
+    /// The `app_capture_overrides` strategy for the app owning the window, with that app's pid.
+    fn capture_strategy(window_id: i64) -> (super::CaptureStrategy, Option<i64>) {
+        if super::config().app_capture_overrides.is_empty() {
+            return (super::CaptureStrategy::Default, None);
+        }
+        let Some(dict) = window_description(window_id) else {
+            return (super::CaptureStrategy::Default, None);
+        };
+        let pid = number_for_key(&dict, unsafe { kCGWindowOwnerPID });
+        let strategy = string_for_key(&dict, unsafe { kCGWindowOwnerName })
+            .and_then(|owner| super::config().app_capture_overrides.get(&owner).copied())
+            .unwrap_or_default();
+        (strategy, pid)
+    }
+
+    /// How long one ScreenCaptureKit completion handler may take before the capture gives up.
+    const SCK_TIMEOUT: Duration = Duration::from_millis(1500);
+
+    /// How long a fetched `SCShareableContent` is reused, so one listing's captures share it.
+    const SHAREABLE_CONTENT_TTL: Duration = Duration::from_secs(2);
+
+    /// Retained `SCShareableContent` (as an address) and when it was fetched.
+    static SHAREABLE_CONTENT: Mutex<Option<(usize, Instant)>> = Mutex::new(None);
+
+    /// Loads ScreenCaptureKit at runtime (it is not linked, so older systems still launch) and
+    /// checks for `SCScreenshotManager`, which only exists on macOS 14 and newer.
+    pub fn screencapturekit_available() -> bool {
+        static AVAILABLE: LazyLock<bool> = LazyLock::new(|| unsafe {
+            let path = CFString::new("/System/Library/Frameworks/ScreenCaptureKit.framework");
+            let bundle: id = msg_send![class!(NSBundle), bundleWithPath: path.as_concrete_TypeRef() as id];
+            if bundle == nil {
+                return false;
+            }
+            let loaded: BOOL = msg_send![bundle, load];
+            let available = loaded == YES && Class::get("SCScreenshotManager").is_some();
+            log::info!("[thumbnail] ScreenCaptureKit available: {}", available);
+            available
+        });
+        *AVAILABLE
+    }
+
+    /// The current shareable content, retained for the caller (who must release it).
+    /// The cache lock is not held while waiting on SCK, so parallel captures do not queue
+    /// behind one slow fetch; concurrent misses may each fetch, and the last one is kept.
+    unsafe fn shareable_content() -> Option<id> {
+        {
+            let mut cached = SHAREABLE_CONTENT.lock().unwrap();
+            if let Some((content, fetched_at)) = *cached {
+                if fetched_at.elapsed() < SHAREABLE_CONTENT_TTL {
+                    let content: id = msg_send![content as id, retain];
+                    return Some(content);
+                }
+                let _: () = msg_send![content as id, release];
+                *cached = None;
+            }
+        }
+
+        let (sender, receiver) = std::sync::mpsc::channel::<usize>();
+        let handler = ConcreteBlock::new(move |content: id, _error: id| unsafe {
+            if content == nil {
+                let _ = sender.send(0);
+                return;
+            }
+            let content: id = msg_send![content, retain];
+            if sender.send(content as usize).is_err() {
+                let _: () = msg_send![content, release];
+            }
+        })
+        .copy();
+        let _: () = msg_send![
+            class!(SCShareableContent),
+            getShareableContentExcludingDesktopWindows: YES
+            onScreenWindowsOnly: NO
+            completionHandler: &*handler
+        ];
+        let content = receiver.recv_timeout(SCK_TIMEOUT).ok().filter(|&content| content != 0)? as id;
+        let previous = SHAREABLE_CONTENT
+            .lock()
+            .unwrap()
+            .replace((content as usize, Instant::now()));
+        if let Some((previous, _)) = previous {
+            let _: () = msg_send![previous as id, release];
+        }
+        let content: id = msg_send![content, retain];
+        Some(content)
+    }
+
+    /// Captures a single window with `SCScreenshotManager`, at backing resolution unless
+    /// `nominal`. The returned image is owned by the caller, like `CGWindowListCreateImage`'s.
+    unsafe fn screencapturekit_window_image(window_id: i64, nominal: bool) -> Option<CGImageRef> {
+        if !screencapturekit_available() {
+            return None;
+        }
+        let content = shareable_content()?;
+        let windows: id = msg_send![content, windows];
+        let count: usize = msg_send![windows, count];
+        let window = (0..count)
+            .map(|index| -> id { msg_send![windows, objectAtIndex: index] })
+            .find(|&window| {
+                let found_id: u32 = msg_send![window, windowID];
+                found_id as i64 == window_id
+            });
+        let Some(window) = window else {
+            let _: () = msg_send![content, release];
+            return None;
+        };
+
+        let filter: id = msg_send![class!(SCContentFilter), alloc];
+        let filter: id = msg_send![filter, initWithDesktopIndependentWindow: window];
+        let frame: CGRect = msg_send![window, frame];
+        let scale: f32 = if nominal { 1.0 } else { msg_send![filter, pointPixelScale] };
+        let configuration: id = msg_send![class!(SCStreamConfiguration), new];
+        let _: () = msg_send![configuration, setWidth: (frame.size.width * scale as f64).round() as usize];
+        let _: () = msg_send![configuration, setHeight: (frame.size.height * scale as f64).round() as usize];
+        let _: () = msg_send![configuration, setShowsCursor: NO];
+
+        let (sender, receiver) = std::sync::mpsc::channel::<usize>();
+        let handler = ConcreteBlock::new(move |image: CGImageRef, _error: id| unsafe {
+            if image.is_null() {
+                let _ = sender.send(0);
+                return;
+            }
+            let image = CGImageRetain(image);
+            if sender.send(image as usize).is_err() {
+                CGImageRelease(image);
+            }
+        })
+        .copy();
+        let _: () = msg_send![
+            class!(SCScreenshotManager),
+            captureImageWithFilter: filter
+            configuration: configuration
+            completionHandler: &*handler
+        ];
+        let _: () = msg_send![filter, release];
+        let _: () = msg_send![configuration, release];
+        let _: () = msg_send![content, release];
+
+        let image = receiver.recv_timeout(SCK_TIMEOUT).ok().filter(|&image| image != 0)? as CGImageRef;
+        if CGImageGetWidth(image) == 0 || CGImageGetHeight(image) == 0 {
+            CGImageRelease(image);
+            return None;
+        }
+        Some(image)
+    }
+
+    /// Returns a retained CGImage for the window; the caller must release it.
+    unsafe fn create_window_image(window_id: i64) -> Result<CGImageRef, CaptureError> {
+        let (strategy, _) = capture_strategy(window_id);
+        if strategy == super::CaptureStrategy::Skip {
+            return Err(CaptureError::Skipped);
+        }
+        let resolution = match super::config().capture_source_max_dimension {
+            Some(max_dimension) if exceeds_capture_dimension(window_id, max_dimension) => {
+                log::debug!(
+                    "[thumbnail] window_id={} larger than {}px, capturing at nominal resolution",
+                    window_id,
+                    max_dimension
+                );
+                kCGWindowImageNominalResolution
+            }
+            _ => kCGWindowImageDefault,
+        };
+
+        // The composite strategy always needs CG: ScreenCaptureKit has no "window and below".
+        let backend = super::config().capture_backend;
+        let use_screencapturekit = strategy != super::CaptureStrategy::OnScreenComposite
+            && match backend {
+                super::CaptureBackend::Auto => screencapturekit_available(),
+                super::CaptureBackend::ScreenCaptureKit => true,
+                super::CaptureBackend::CoreGraphics => false,
+            };
+        if use_screencapturekit {
+            let nominal = resolution == kCGWindowImageNominalResolution;
+            if let Some(image) = screencapturekit_window_image(window_id, nominal) {
+                return Ok(image);
+            }
+            if backend == super::CaptureBackend::ScreenCaptureKit {
+                log::debug!("[thumbnail] window_id={} ScreenCaptureKit capture failed", window_id);
+                return Err(CaptureError::NullImage);
+            }
+            log::debug!("[thumbnail] window_id={} ScreenCaptureKit failed, falling back to CG", window_id);
+        }
+        let cg_image = match strategy {
+            super::CaptureStrategy::OnScreenComposite => {
+                let bounds = window_description(window_id)
+                    .and_then(|dict| bounds_for_key(&dict, kCGWindowBounds))
+                    .ok_or(CaptureError::WindowNotFound)?;
+                CGWindowListCreateImage(
+                    CGRect::new(&CGPoint::new(bounds.x, bounds.y), &CGSize::new(bounds.width, bounds.height)),
+                    kCGWindowListOptionOnScreenBelowWindow | kCGWindowListOptionIncludingWindow,
+                    window_id as CGWindowID,
+                    resolution,
+                )
+            }
+            _ => CGWindowListCreateImage(
+                cg_rect_null(),
+                kCGWindowListOptionIncludingWindow,
+                window_id as CGWindowID,
+                kCGWindowImageBoundsIgnoreFraming | resolution,
+            ),
+        };
+
+        if cg_image.is_null() {
+            return Err(CaptureError::NullImage);
+        }
+
+        if CGImageGetWidth(cg_image) == 0 || CGImageGetHeight(cg_image) == 0 {
+            CGImageRelease(cg_image);
+            return Err(CaptureError::ZeroSize);
+        }
+
+        Ok(cg_image)
+    }
+
+    /// CG-only capture of a window that is not on screen, ignoring the configured backend and
+    /// strategy (ScreenCaptureKit and the on-screen composite both need the window on screen).
+    /// Returns a retained CGImage; the caller must release it.
+    unsafe fn create_offscreen_window_image(window_id: i64) -> Result<CGImageRef, CaptureError> {
+        let cg_image = CGWindowListCreateImage(
+            cg_rect_null(),
+            kCGWindowListOptionAll | kCGWindowListOptionIncludingWindow,
+            window_id as CGWindowID,
+            kCGWindowImageBoundsIgnoreFraming | kCGWindowImageNominalResolution,
+        );
+        if cg_image.is_null() {
+            return Err(CaptureError::NullImage);
+        }
+        if CGImageGetWidth(cg_image) == 0 || CGImageGetHeight(cg_image) == 0 {
+            CGImageRelease(cg_image);
+            return Err(CaptureError::ZeroSize);
+        }
+        Ok(cg_image)
+    }
+
+    /// Whether a default (backing-resolution) capture of the window would be larger than
+    /// `max_dimension` pixels, judged from its bounds and its display's backing scale.
+    fn exceeds_capture_dimension(window_id: i64, max_dimension: u32) -> bool {
+        let Some(bounds) = window_description(window_id).and_then(|dict| bounds_for_key(&dict, unsafe { kCGWindowBounds })) else {
+            return false;
+        };
+        let displays = displays();
+        let scale = super::display_for_bounds(&bounds, &displays)
+            .and_then(|id| displays.iter().find(|display| display.id == id))
+            .filter(|display| display.bounds.width > 0.0)
+            .map(|display| CGDisplay::new(display.id).pixels_wide() as f64 / display.bounds.width)
+            .unwrap_or(2.0);
+        bounds.width.max(bounds.height) * scale > max_dimension as f64
+    }
+
+    /// Scales `cg_image` down to at most `max_width` and encodes it as a JPEG thumbnail,
+    /// using the configured scaler. Does not take ownership of the image.
+    unsafe fn encode_scaled_image(cg_image: CGImageRef, max_width: u32) -> Result<EncodedThumbnail, CaptureError> {
+        let scaler = super::config().scaler;
+        match scaler.filter() {
+            None => encode_scaled_image_cg(cg_image, max_width),
+            Some(filter) => {
+                let start = Instant::now();
+                let full = cg_image_to_rgba(cg_image).ok_or(CaptureError::ContextCreationFailed)?;
+                let scaled = super::resize_rgba(&full, max_width, filter);
+                let thumbnail = super::encode_rgba_thumbnail(&scaled).ok_or(CaptureError::EncodeFailed);
+                log::debug!(
+                    "[thumbnail] scaler={:?} {}x{} -> {}x{} {}ms",
+                    scaler,
+                    full.width(),
+                    full.height(),
+                    scaled.width(),
+                    scaled.height(),
+                    start.elapsed().as_millis()
+                );
+                thumbnail
+            }
+        }
+    }
+
+    /// Creates (+1) the configured thumbnail color space. A window capture is tagged with its
+    /// display's color space, so `DisplayNative` reuses the image's own.
+    unsafe fn thumbnail_color_space(cg_image: CGImageRef) -> *const std::ffi::c_void {
+        let color_space = match super::config().thumbnail_color_space {
+            super::ThumbnailColorSpace::DeviceRgb => std::ptr::null(),
+            super::ThumbnailColorSpace::Srgb => CGColorSpaceCreateWithName(kCGColorSpaceSRGB),
+            super::ThumbnailColorSpace::DisplayNative => {
+                let native = CGImageGetColorSpace(cg_image);
+                if native.is_null() {
+                    native
+                } else {
+                    CGColorSpaceRetain(native)
+                }
+            }
+        };
+        if color_space.is_null() {
+            CGColorSpaceCreateDeviceRGB()
+        } else {
+            color_space
+        }
+    }
+
+    /// Draws `cg_image` into an RGBA bitmap context of `width`x`height` and returns the context.
+    /// The caller must release the returned context.
+    unsafe fn draw_into_rgba_context(
+        cg_image: CGImageRef,
+        width: usize,
+        height: usize,
+    ) -> Option<*const std::ffi::c_void> {
+        let color_space = thumbnail_color_space(cg_image);
+        let context = CGBitmapContextCreate(
+            std::ptr::null_mut(),
+            width,
+            height,
+            8,
+            width * 4,
+            color_space,
+            kCGImageAlphaPremultipliedLast | kCGBitmapByteOrder32Big,
+        );
+        CGColorSpaceRelease(color_space);
+
+        if context.is_null() {
+            return None;
+        }
+
+        // Set high quality interpolation, unless throttled for battery
+        let quality = if super::BATTERY_SAVING.load(std::sync::atomic::Ordering::SeqCst) {
+            kCGInterpolationLow
+        } else {
+            kCGInterpolationHigh
+        };
+        CGContextSetInterpolationQuality(context, quality);
+
+        let rect = CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: CGSize { width: width as f64, height: height as f64 },
+        };
+        CGContextDrawImage(context, rect, cg_image);
+        Some(context)
+    }
+
+    /// Decodes the full-resolution image into an `RgbaImage` for software scaling.
+    unsafe fn cg_image_to_rgba(cg_image: CGImageRef) -> Option<image::RgbaImage> {
+        let width = CGImageGetWidth(cg_image);
+        let height = CGImageGetHeight(cg_image);
+        let context = draw_into_rgba_context(cg_image, width, height)?;
+
+        let data_ptr = CGBitmapContextGetData(context) as *const u8;
+        if data_ptr.is_null() {
+            CGContextRelease(context);
+            return None;
+        }
+        let pixels = std::slice::from_raw_parts(data_ptr, width * height * 4).to_vec();
+        CGContextRelease(context);
+
+        image::RgbaImage::from_raw(width as u32, height as u32, pixels)
+    }
+
+    /// Hardware-accelerated path: CG scales while drawing into the target-size context.
+    unsafe fn encode_scaled_image_cg(cg_image: CGImageRef, max_width: u32) -> Result<EncodedThumbnail, CaptureError> {
+        let width = CGImageGetWidth(cg_image);
+        let height = CGImageGetHeight(cg_image);
+
+        // Calculate target dimensions
+        let (new_width, new_height) = super::thumbnail_size(width as u32, height as u32, max_width);
+        let (new_width, new_height) = (new_width as usize, new_height as usize);
+
+        // Draw the image scaled to target size
+        let context =
+            draw_into_rgba_context(cg_image, new_width, new_height).ok_or(CaptureError::ContextCreationFailed)?;
+
+        // Get pixel data directly from context (already in RGBA format)
+        let data_ptr = CGBitmapContextGetData(context) as *const u8;
+        if data_ptr.is_null() {
+            CGContextRelease(context);
+            return Err(CaptureError::ContextCreationFailed);
+        }
 
-    pub fn has_screen_recording_permission() -> bool {
-        unsafe { CGPreflightScreenCaptureAccess() }
+        // Convert RGBA to RGB for JPEG
+        let pixel_count = new_width * new_height;
+        let mut rgb_data = Vec::with_capacity(pixel_count * 3);
+        for i in 0..pixel_count {
+            let offset = i * 4;
+            rgb_data.push(*data_ptr.add(offset));     // R
+            rgb_data.push(*data_ptr.add(offset + 1)); // G
+            rgb_data.push(*data_ptr.add(offset + 2)); // B
+        }
+
+        CGContextRelease(context);
+
+        super::encode_thumbnail(&rgb_data, new_width as u32, new_height as u32).ok_or(CaptureError::EncodeFailed)
     }
 
-    #[link(name = "Carbon", kind = "framework")]
-    extern "C" {
-        fn TISCopyInputSourceForLanguage(language: CFStringRef) -> CFTypeRef;
-        fn TISSelectInputSource(input_source: CFTypeRef) -> i32;
+    /// Copies a string-valued AX attribute of `element`.
+    unsafe fn ax_string_attribute(element: AXUIElementRef, attribute: &str) -> Option<String> {
+        ax_attribute(element, attribute)?
+            .downcast::<CFString>()
+            .map(|s| s.to_string())
     }
 
-    pub fn switch_to_english_input() {
-        unsafe {
-            let lang = CFString::new("en");
-            let source = TISCopyInputSourceForLanguage(lang.as_concrete_TypeRef());
-            if !source.is_null() {
-                TISSelectInputSource(source);
-                CFRelease(source);
-            }
+    /// Copies an AX attribute of `element` as an owned CF value (released on drop).
+    unsafe fn ax_attribute(element: AXUIElementRef, attribute: &str) -> Option<CFType> {
+        let key = CFString::new(attribute);
+        let mut value: CFTypeRef = std::ptr::null();
+        let err = AXUIElementCopyAttributeValue(element, key.as_concrete_TypeRef(), &mut value);
+        if err != kAXErrorSuccess || value.is_null() {
+            return None;
         }
+        Some(CFType::wrap_under_create_rule(value))
     }
 
-    pub fn capture_window_thumbnail(window_id: i64, max_width: u32) -> Option<String> {
-        let start = Instant::now();
+    /// Copies an array-valued AX attribute (`AXWindows`, `AXChildren`, ...).
+    unsafe fn ax_array_attribute(element: AXUIElementRef, attribute: &str) -> Option<CFArray<CFType>> {
+        let value = ax_attribute(element, attribute)?;
+        if !value.instance_of::<CFArray<CFType>>() {
+            return None;
+        }
+        Some(CFArray::wrap_under_get_rule(value.as_CFTypeRef() as _))
+    }
 
-        unsafe {
-            let cg_image = CGWindowListCreateImage(
-                cg_rect_null(),
-                kCGWindowListOptionIncludingWindow,
-                window_id as CGWindowID,
-                kCGWindowImageBoundsIgnoreFraming | kCGWindowImageDefault,
-            );
+    /// Reads `AXPosition` + `AXSize` of an AX element as global screen bounds.
+    unsafe fn ax_frame(element: AXUIElementRef) -> Option<WindowBounds> {
+        let position = ax_attribute(element, "AXPosition")?;
+        let size = ax_attribute(element, "AXSize")?;
+        let mut point = CGPoint::new(0.0, 0.0);
+        let mut extent = CGSize::new(0.0, 0.0);
+        let ok = AXValueGetValue(position.as_CFTypeRef(), kAXValueCGPointType, &mut point as *mut _ as *mut _)
+            && AXValueGetValue(size.as_CFTypeRef(), kAXValueCGSizeType, &mut extent as *mut _ as *mut _);
+        ok.then(|| WindowBounds {
+            x: point.x,
+            y: point.y,
+            width: extent.width,
+            height: extent.height,
+        })
+    }
 
-            if cg_image.is_null() {
-                return None;
-            }
+    /// The create/release calls behind `OwnedAXElement`; tests swap in counting stubs.
+    #[cfg(not(test))]
+    mod ax_ownership {
+        use super::{AXUIElementCreateApplication, AXUIElementRef, CFRelease, CFTypeRef};
 
-            let width = CGImageGetWidth(cg_image);
-            let height = CGImageGetHeight(cg_image);
+        pub unsafe fn create_application(pid: i32) -> AXUIElementRef {
+            AXUIElementCreateApplication(pid)
+        }
 
-            if width == 0 || height == 0 {
-                CGImageRelease(cg_image);
-                return None;
-            }
+        pub unsafe fn release(element: AXUIElementRef) {
+            CFRelease(element as CFTypeRef)
+        }
+    }
 
-            // Calculate target dimensions
-            let (new_width, new_height) = if width > max_width as usize {
-                let ratio = max_width as f32 / width as f32;
-                (max_width as usize, (height as f32 * ratio) as usize)
-            } else {
-                (width, height)
-            };
+    #[cfg(test)]
+    mod ax_ownership {
+        use super::AXUIElementRef;
+        use std::cell::Cell;
 
-            // Use CGContext for hardware-accelerated high-quality scaling
-            let color_space = CGColorSpaceCreateDeviceRGB();
-            let context = CGBitmapContextCreate(
-                std::ptr::null_mut(),
-                new_width,
-                new_height,
-                8,
-                new_width * 4,
-                color_space,
-                kCGImageAlphaPremultipliedLast | kCGBitmapByteOrder32Big,
-            );
-            CGColorSpaceRelease(color_space);
+        thread_local! {
+            pub static CREATED: Cell<usize> = const { Cell::new(0) };
+            pub static RELEASED: Cell<usize> = const { Cell::new(0) };
+        }
 
-            if context.is_null() {
-                CGImageRelease(cg_image);
-                return None;
+        /// A fake non-null handle for positive pids, null otherwise (as for a failed create).
+        pub unsafe fn create_application(pid: i32) -> AXUIElementRef {
+            if pid <= 0 {
+                return std::ptr::null();
             }
+            CREATED.with(|created| created.set(created.get() + 1));
+            pid as usize as AXUIElementRef
+        }
 
-            // Set high quality interpolation
-            CGContextSetInterpolationQuality(context, kCGInterpolationHigh);
+        pub unsafe fn release(_element: AXUIElementRef) {
+            RELEASED.with(|released| released.set(released.get() + 1));
+        }
+    }
 
-            // Draw the image scaled to target size
-            let rect = CGRect {
-                origin: CGPoint { x: 0.0, y: 0.0 },
-                size: CGSize { width: new_width as f64, height: new_height as f64 },
-            };
-            CGContextDrawImage(context, rect, cg_image);
-            CGImageRelease(cg_image);
+    /// An AX element we created (+1 retained), released exactly once on drop.
+    struct OwnedAXElement(AXUIElementRef);
 
-            // Get pixel data directly from context (already in RGBA format)
-            let data_ptr = CGBitmapContextGetData(context) as *const u8;
-            if data_ptr.is_null() {
-                CGContextRelease(context);
-                return None;
-            }
+    impl OwnedAXElement {
+        fn application(pid: i32) -> Option<Self> {
+            let element = unsafe { ax_ownership::create_application(pid) };
+            (!element.is_null()).then_some(Self(element))
+        }
 
-            // Convert RGBA to RGB for JPEG
-            let pixel_count = new_width * new_height;
-            let mut rgb_data = Vec::with_capacity(pixel_count * 3);
-            for i in 0..pixel_count {
-                let offset = i * 4;
-                rgb_data.push(*data_ptr.add(offset));     // R
-                rgb_data.push(*data_ptr.add(offset + 1)); // G
-                rgb_data.push(*data_ptr.add(offset + 2)); // B
-            }
+        fn as_raw(&self) -> AXUIElementRef {
+            self.0
+        }
+    }
 
-            CGContextRelease(context);
+    impl Drop for OwnedAXElement {
+        fn drop(&mut self) {
+            unsafe { ax_ownership::release(self.0) }
+        }
+    }
 
-            // Encode to JPEG
-            let mut jpeg_data = Vec::with_capacity(pixel_count * 3 / 4);
-            if image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg_data, 80)
-                .write_image(
-                    &rgb_data,
-                    new_width as u32,
-                    new_height as u32,
-                    image::ExtendedColorType::Rgb8,
-                )
-                .is_err()
-            {
-                return None;
+    /// (parent window frame, sheet frame) for every AX sheet attached to a window of `pid`.
+    fn ax_sheet_frames(pid: i32) -> Vec<(WindowBounds, WindowBounds)> {
+        let mut frames = Vec::new();
+        let Some(app) = OwnedAXElement::application(pid) else {
+            return frames;
+        };
+        unsafe {
+            if let Some(windows) = ax_array_attribute(app.as_raw(), "AXWindows") {
+                for window in windows.iter() {
+                    let window_ref = window.as_CFTypeRef() as AXUIElementRef;
+                    let Some(parent_frame) = ax_frame(window_ref) else {
+                        continue;
+                    };
+                    let Some(children) = ax_array_attribute(window_ref, "AXChildren") else {
+                        continue;
+                    };
+                    for child in children.iter() {
+                        let child_ref = child.as_CFTypeRef() as AXUIElementRef;
+                        if ax_string_attribute(child_ref, "AXRole").as_deref() != Some("AXSheet") {
+                            continue;
+                        }
+                        if let Some(sheet_frame) = ax_frame(child_ref) {
+                            frames.push((parent_frame, sheet_frame));
+                        }
+                    }
+                }
             }
+        }
+        frames
+    }
 
-            let base64_str = general_purpose::STANDARD.encode(&jpeg_data);
-            let data_url = format!("data:image/jpeg;base64,{}", base64_str);
+    /// Matches AX sheets to CG entries by frame and sets `parent_id` on the sheet entries.
+    fn assign_sheet_parents(entries: &mut [MacWindowEntry]) {
+        let mut frames_by_pid: HashMap<i64, Vec<(WindowBounds, WindowBounds)>> = HashMap::new();
+        let mut assignments = Vec::new();
 
-            let elapsed = start.elapsed().as_millis();
-            if elapsed > 50 {
-                println!("[thumbnail] window_id={} {}ms", window_id, elapsed);
+        for (index, entry) in entries.iter().enumerate() {
+            let (Some(pid), Some(bounds)) = (entry.owner_pid, entry.bounds) else {
+                continue;
+            };
+            // A sheet needs a sibling window of the same process to belong to.
+            if entries.iter().filter(|other| other.owner_pid == Some(pid)).count() < 2 {
+                continue;
+            }
+            let frames = frames_by_pid
+                .entry(pid)
+                .or_insert_with(|| ax_sheet_frames(pid as i32));
+            let Some((parent_frame, _)) = frames.iter().find(|(_, sheet)| sheet.approx_eq(&bounds, 2.0)) else {
+                continue;
+            };
+            let parent = entries.iter().find(|other| {
+                other.owner_pid == Some(pid)
+                    && other.id != entry.id
+                    && other.bounds.is_some_and(|b| b.approx_eq(parent_frame, 2.0))
+            });
+            if let Some(parent) = parent {
+                assignments.push((index, parent.id.clone()));
             }
+        }
 
-            Some(data_url)
+        for (index, parent_id) in assignments {
+            entries[index].parent_id = Some(parent_id);
         }
     }
 
-    fn activate_window_by_title(pid: i32, window_title: &str) -> Result<(), String> {
+    /// Maps AX window title → document path for every window of `pid` that exposes `AXDocument`.
+    fn ax_window_documents(pid: i32) -> HashMap<String, String> {
+        let mut documents = HashMap::new();
+        let Some(app) = OwnedAXElement::application(pid) else {
+            return documents;
+        };
         unsafe {
-            // Create AXUIElement for the application
-            let app_ref = AXUIElementCreateApplication(pid);
-            if app_ref.is_null() {
-                return Err("Failed to create AXUIElement".into());
+            if let Some(windows) = ax_array_attribute(app.as_raw(), "AXWindows") {
+                for window in windows.iter() {
+                    let window_ref = window.as_CFTypeRef() as AXUIElementRef;
+                    let (Some(title), Some(document)) = (
+                        ax_string_attribute(window_ref, "AXTitle"),
+                        ax_string_attribute(window_ref, "AXDocument"),
+                    ) else {
+                        continue;
+                    };
+                    documents.insert(title, file_url_to_path(&document));
+                }
             }
+        }
+        documents
+    }
 
-            // Get the windows array
-            let windows_key = CFString::new("AXWindows");
-            let mut windows_ref: CFTypeRef = std::ptr::null();
-
-            let err = AXUIElementCopyAttributeValue(
-                app_ref,
-                windows_key.as_concrete_TypeRef(),
-                &mut windows_ref,
-            );
-
-            if err != kAXErrorSuccess {
-                CFRelease(app_ref as CFTypeRef);
-                return Err(format!("Failed to get windows (AX error {})", err));
+    /// `AXDocument` is usually a `file://` URL; turn it into a plain, percent-decoded path.
+    fn file_url_to_path(url: &str) -> String {
+        let Some(rest) = url.strip_prefix("file://") else {
+            return url.to_string();
+        };
+        let bytes = rest.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'%' && i + 2 < bytes.len() {
+                let byte = std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = byte {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                }
             }
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+        String::from_utf8_lossy(&decoded).into_owned()
+    }
 
-            if windows_ref.is_null() {
-                CFRelease(app_ref as CFTypeRef);
-                return Err("Windows array is null".into());
+    /// Sets `AXPosition` and `AXSize` of the AX window of `pid` at `current` (matched by frame,
+    /// else by title) to `frame`. Position goes first so the resize is not clipped by the screen.
+    fn set_ax_window_frame(
+        pid: i32,
+        current: Option<WindowBounds>,
+        title: &str,
+        frame: WindowBounds,
+    ) -> Result<(), String> {
+        guard_ffi("set_ax_window_frame", Err("panicked while moving the window".into()), || {
+            unsafe {
+                let window = find_ax_window(pid, current, title)?;
+                let window_ref = window.as_CFTypeRef() as AXUIElementRef;
+
+                let set_value = |attribute: &str, value_type: u32, value: *const std::ffi::c_void| {
+                    let value = AXValueCreate(value_type, value);
+                    if value.is_null() {
+                        return false;
+                    }
+                    let attribute = CFString::new(attribute);
+                    let err = AXUIElementSetAttributeValue(window_ref, attribute.as_concrete_TypeRef(), value);
+                    CFRelease(value);
+                    err == kAXErrorSuccess
+                };
+                let position = CGPoint::new(frame.x, frame.y);
+                let size = CGSize::new(frame.width, frame.height);
+                let moved = set_value("AXPosition", kAXValueCGPointType, &position as *const _ as *const _);
+                let resized = set_value("AXSize", kAXValueCGSizeType, &size as *const _ as *const _);
+                if moved && resized {
+                    Ok(())
+                } else {
+                    Err(format!("AX refused the frame (moved={moved}, resized={resized})"))
+                }
             }
+        })
+    }
 
-            let window_count = CFArrayGetCount(windows_ref);
-            let title_key = CFString::new("AXTitle");
-            let raise_action = CFString::new("AXRaise");
-
-            let mut found = false;
+    /// The AX window of `pid` at `current` (matched by frame), else the one titled `title`.
+    unsafe fn find_ax_window(pid: i32, current: Option<WindowBounds>, title: &str) -> Result<CFType, String> {
+        let app = OwnedAXElement::application(pid).ok_or("Failed to create AXUIElement")?;
+        let windows = ax_array_attribute(app.as_raw(), "AXWindows").ok_or("Failed to get windows")?;
+        let windows: Vec<CFType> = windows.iter().map(|window| window.clone()).collect();
+        let by_frame = windows.iter().position(|window| {
+            current.is_some_and(|current| {
+                ax_frame(window.as_CFTypeRef() as AXUIElementRef).is_some_and(|ax| ax.approx_eq(&current, 2.0))
+            })
+        });
+        let index = by_frame
+            .or_else(|| {
+                windows.iter().position(|window| {
+                    ax_string_attribute(window.as_CFTypeRef() as AXUIElementRef, "AXTitle").as_deref() == Some(title)
+                })
+            })
+            .ok_or("AX window not found")?;
+        Ok(windows[index].clone())
+    }
 
-            // Iterate through all windows
-            for i in 0..window_count {
-                let window_ref = CFArrayGetValueAtIndex(windows_ref, i);
-                if window_ref.is_null() {
-                    continue;
-                }
+    /// How many levels below the window `ax_tree` descends.
+    const AX_DUMP_MAX_DEPTH: usize = 8;
+    /// Elements `ax_tree` serializes at most; complex apps (browsers, IDEs) have thousands.
+    const AX_DUMP_MAX_NODES: usize = 2000;
+
+    /// Role, subrole, title and frame of `element` and, within the depth and node budget, of
+    /// its descendants. Cut-off subtrees are marked `truncated` with their `childCount`.
+    unsafe fn ax_dump_node(element: AXUIElementRef, depth: usize, budget: &mut usize) -> serde_json::Value {
+        *budget = budget.saturating_sub(1);
+        let mut node = serde_json::json!({
+            "role": ax_string_attribute(element, "AXRole"),
+            "subrole": ax_string_attribute(element, "AXSubrole"),
+            "title": ax_string_attribute(element, "AXTitle"),
+            "frame": ax_frame(element),
+        });
+        let Some(children) = ax_array_attribute(element, "AXChildren") else {
+            return node;
+        };
+        if children.len() == 0 {
+            return node;
+        }
+        if depth >= AX_DUMP_MAX_DEPTH {
+            node["childCount"] = children.len().into();
+            node["truncated"] = true.into();
+            return node;
+        }
+        let mut dumped = Vec::new();
+        for child in children.iter() {
+            if *budget == 0 {
+                node["childCount"] = children.len().into();
+                node["truncated"] = true.into();
+                break;
+            }
+            dumped.push(ax_dump_node(child.as_CFTypeRef() as AXUIElementRef, depth + 1, budget));
+        }
+        node["children"] = dumped.into();
+        node
+    }
 
-                // Get the window title
-                let mut title_ref: CFTypeRef = std::ptr::null();
-                let err = AXUIElementCopyAttributeValue(
-                    window_ref as AXUIElementRef,
-                    title_key.as_concrete_TypeRef(),
-                    &mut title_ref,
-                );
+    /// The AX hierarchy of the window `pid` shows at `current` / titled `title`. When no AX
+    /// window matches, the error lists the titles AX does report, which is what title-based
+    /// activation compares against.
+    fn ax_tree(pid: i32, current: Option<WindowBounds>, title: &str) -> Result<serde_json::Value, String> {
+        guard_ffi("ax_tree", Err("panicked while walking the AX tree".into()), || unsafe {
+            let window = find_ax_window(pid, current, title).map_err(|error| {
+                let titles: Vec<Option<String>> = OwnedAXElement::application(pid)
+                    .and_then(|app| ax_array_attribute(app.as_raw(), "AXWindows"))
+                    .map(|windows| {
+                        windows
+                            .iter()
+                            .map(|window| ax_string_attribute(window.as_CFTypeRef() as AXUIElementRef, "AXTitle"))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                format!("{error} (looked for {title:?}; AX windows: {titles:?})")
+            })?;
+            let mut budget = AX_DUMP_MAX_NODES;
+            Ok(ax_dump_node(window.as_CFTypeRef() as AXUIElementRef, 0, &mut budget))
+        })
+    }
 
-                if err == kAXErrorSuccess && !title_ref.is_null() {
-                    // Convert to Rust string
-                    let title_cfstring = CFString::wrap_under_get_rule(title_ref as _);
-                    let title = title_cfstring.to_string();
+    /// Raises the AX window of `pid` titled `window_title`. With `bounds`, a window at that
+    /// frame is preferred, so a same-titled sibling is not raised instead.
+    fn activate_window_by_title(pid: i32, window_title: &str, bounds: Option<WindowBounds>) -> Result<(), String> {
+        guard_ffi("activate_window_by_title", Err("panicked while raising the window".into()), || {
+            let app = OwnedAXElement::application(pid).ok_or("Failed to create AXUIElement")?;
+            let raise_action = super::config().raise_action;
+            unsafe {
+                let windows = ax_array_attribute(app.as_raw(), "AXWindows").ok_or("Failed to get windows")?;
+
+                if let Some(bounds) = bounds {
+                    let at_frame = windows.iter().find(|window| {
+                        let window_ref = window.as_CFTypeRef() as AXUIElementRef;
+                        ax_frame(window_ref).is_some_and(|frame| frame.approx_eq(&bounds, 2.0))
+                            && ax_string_attribute(window_ref, "AXTitle").is_some_and(|title| title.contains(window_title))
+                    });
+                    if let Some(window) = at_frame {
+                        if bring_window_forward(window.as_CFTypeRef() as AXUIElementRef, raise_action) {
+                            return Ok(());
+                        }
+                    }
+                }
 
-                    // Release the title
-                    CFRelease(title_ref);
+                // Iterate through all windows
+                for window in windows.iter() {
+                    let window_ref = window.as_CFTypeRef() as AXUIElementRef;
+                    let Some(title) = ax_string_attribute(window_ref, "AXTitle") else {
+                        continue;
+                    };
 
                     // Check if this is the window we're looking for
-                    if title.contains(window_title) {
-                        // Perform the raise action
-                        let err = AXUIElementPerformAction(
-                            window_ref as AXUIElementRef,
-                            raise_action.as_concrete_TypeRef(),
-                        );
-
-                        if err == kAXErrorSuccess {
-                            found = true;
-                            break;
-                        }
+                    if title.contains(window_title) && bring_window_forward(window_ref, raise_action) {
+                        return Ok(());
                     }
                 }
             }
+            Err("Window not found or could not be raised".into())
+        })
+    }
 
-            // Clean up
-            CFRelease(windows_ref);
-            CFRelease(app_ref as CFTypeRef);
+    /// Applies `action` to an AX window; true if any of its steps succeeded.
+    unsafe fn bring_window_forward(window_ref: AXUIElementRef, action: super::RaiseAction) -> bool {
+        use super::RaiseAction;
 
-            if found {
-                Ok(())
-            } else {
-                Err("Window not found or could not be raised".into())
+        let raise = || {
+            let raise_action = CFString::new("AXRaise");
+            AXUIElementPerformAction(window_ref, raise_action.as_concrete_TypeRef()) == kAXErrorSuccess
+        };
+        let make_main = || {
+            let set_true = |attribute: &str| {
+                let attribute = CFString::new(attribute);
+                AXUIElementSetAttributeValue(
+                    window_ref,
+                    attribute.as_concrete_TypeRef(),
+                    CFBoolean::true_value().as_CFTypeRef(),
+                ) == kAXErrorSuccess
+            };
+            // Both are attempted: some apps only honor one of them.
+            let main = set_true("AXMain");
+            let focused = set_true("AXFocused");
+            main || focused
+        };
+
+        match action {
+            RaiseAction::Raise => raise(),
+            RaiseAction::Main => make_main(),
+            RaiseAction::RaiseThenMain => {
+                let raised = raise();
+                make_main() || raised
             }
         }
     }
@@ -848,179 +6146,385 @@ mod macos {
         }
     }
 
-    impl WindowProvider for MacWindowProvider {
-        fn list(&self, capture_thumbnails: bool) -> Vec<WindowInfo> {
-            let started_at = Instant::now();
-            let options = kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements;
-            let current_pid = std::process::id() as i64;
-
-            let ids_start = Instant::now();
-            let Some(window_ids) = create_window_list(options, kCGNullWindowID) else {
-                println!(
-                    "[rifthold][macos] list_windows failed (window ids); elapsed={}ms",
-                    started_at.elapsed().as_millis()
-                );
-                return Vec::new();
-            };
-            let ids_elapsed = ids_start.elapsed().as_millis();
+    /// Mirror of `struct rusage_info_v0`; times are in mach absolute time units.
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct RusageInfoV0 {
+        uuid: [u8; 16],
+        user_time: u64,
+        system_time: u64,
+        pkg_idle_wkups: u64,
+        interrupt_wkups: u64,
+        pageins: u64,
+        wired_size: u64,
+        resident_size: u64,
+        phys_footprint: u64,
+        proc_start_abstime: u64,
+        proc_exit_abstime: u64,
+    }
 
-            let desc_start = Instant::now();
-            let Some(descriptions) = create_description_from_array(window_ids) else {
-                println!(
-                    "[rifthold][macos] list_windows failed (descriptions); ids_ms={}",
-                    ids_elapsed
-                );
-                return Vec::new();
-            };
-            let desc_elapsed = desc_start.elapsed().as_millis();
-
-            let iter_start = Instant::now();
-            let window_number_key = unsafe { kCGWindowNumber };
-            let owner_name_key = unsafe { kCGWindowOwnerName };
-            let window_name_key = unsafe { kCGWindowName };
-            let owner_pid_key = unsafe { kCGWindowOwnerPID };
-            let layer_key = unsafe { kCGWindowLayer };
-
-            let mut fallback_count = 0;
-            let mut skipped_layers = 0;
-            let mut skipped_self = 0;
-            let mut skipped_control_center = 0;
-
-            // First pass: collect all window info and identify apps needing title fetch
-            let mut pending_entries = Vec::new();
-            for dict in descriptions.iter() {
-                let Some(window_number) = number_for_key(&dict, window_number_key) else {
-                    continue;
-                };
+    #[repr(C)]
+    struct MachTimebaseInfo {
+        numer: u32,
+        denom: u32,
+    }
 
-                let id = window_number.to_string();
-                let app_name =
-                    string_for_key(&dict, owner_name_key).unwrap_or_else(|| "App".into());
-                let cg_title = string_for_key(&dict, window_name_key);
-                let owner_pid = number_for_key(&dict, owner_pid_key);
-                let layer = number_for_key(&dict, layer_key).unwrap_or(0);
+    const RUSAGE_INFO_V0: i32 = 0;
 
-                if owner_pid == Some(current_pid) {
-                    skipped_self += 1;
-                    continue;
-                }
+    extern "C" {
+        fn proc_pid_rusage(pid: i32, flavor: i32, buffer: *mut std::ffi::c_void) -> i32;
+        fn mach_timebase_info(info: *mut MachTimebaseInfo) -> i32;
+    }
 
-                if layer != 0 {
-                    skipped_layers += 1;
-                    continue;
-                }
+    #[derive(Clone, Copy)]
+    struct ProcessResources {
+        /// `None` on the first sample of a process, since CPU% needs two samples.
+        cpu_percent: Option<f64>,
+        memory_mb: f64,
+    }
 
-                if app_name == "Control Center" {
-                    skipped_control_center += 1;
-                    continue;
-                }
+    /// Previous (cpu time ns, wall clock) per pid, to turn cumulative CPU time into a percentage.
+    static CPU_SAMPLES: LazyLock<Mutex<HashMap<i64, (u64, Instant)>>> =
+        LazyLock::new(|| Mutex::new(HashMap::new()));
 
-                pending_entries.push((id, app_name, cg_title, owner_pid));
-            }
+    fn sample_process(pid: i64) -> Option<ProcessResources> {
+        let mut usage = std::mem::MaybeUninit::<RusageInfoV0>::zeroed();
+        if unsafe { proc_pid_rusage(pid as i32, RUSAGE_INFO_V0, usage.as_mut_ptr() as *mut _) } != 0 {
+            return None;
+        }
+        let usage = unsafe { usage.assume_init() };
+
+        let mut timebase = MachTimebaseInfo { numer: 1, denom: 1 };
+        unsafe { mach_timebase_info(&mut timebase) };
+        let cpu_ns = (usage.user_time + usage.system_time) as u128 * timebase.numer as u128
+            / timebase.denom.max(1) as u128;
+        let cpu_ns = cpu_ns as u64;
+
+        let now = Instant::now();
+        let previous = CPU_SAMPLES.lock().unwrap().insert(pid, (cpu_ns, now));
+        let cpu_percent = previous.and_then(|(previous_ns, previous_at)| {
+            let wall_ns = now.duration_since(previous_at).as_nanos() as f64;
+            (wall_ns > 0.0).then(|| cpu_ns.saturating_sub(previous_ns) as f64 / wall_ns * 100.0)
+        });
+
+        Some(ProcessResources {
+            cpu_percent,
+            memory_mb: usage.phys_footprint as f64 / (1024.0 * 1024.0),
+        })
+    }
 
-            // Second pass: build window entries with CG titles
-            let mut entries = Vec::new();
+    /// Mirror of libproc's `struct proc_bsdshortinfo`; only `ppid` is read.
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct ProcBsdShortInfo {
+        pid: u32,
+        ppid: u32,
+        pgid: u32,
+        status: u32,
+        comm: [u8; 16],
+        flags: u32,
+        uid: u32,
+        gid: u32,
+        ruid: u32,
+        rgid: u32,
+        svuid: u32,
+        svgid: u32,
+        rfu: u32,
+    }
 
-            for (id, app_name, cg_title, owner_pid) in pending_entries {
-                // Use CG title if available (requires Screen Recording permission)
-                // Otherwise fall back to app name
-                let (title, is_fallback) = if let Some(t) = cg_title.filter(|t| !t.trim().is_empty()) {
-                    (t, false)
-                } else {
-                    fallback_count += 1;
-                    (app_name.clone(), true)
-                };
+    const PROC_PIDT_SHORTBSDINFO: i32 = 13;
 
-                entries.push(MacWindowEntry {
-                    id,
-                    title,
-                    app_name,
-                    is_title_fallback: is_fallback,
-                    owner_pid,
-                });
-            }
+    extern "C" {
+        fn proc_pidinfo(pid: i32, flavor: i32, arg: u64, buffer: *mut std::ffi::c_void, buffersize: i32) -> i32;
+    }
 
-            // Keep the snapshot to resolve activation requests.
-            self.refresh_snapshot(&entries);
+    fn parent_pid(pid: i64) -> Option<i64> {
+        let mut info = std::mem::MaybeUninit::<ProcBsdShortInfo>::zeroed();
+        let size = std::mem::size_of::<ProcBsdShortInfo>() as i32;
+        let written = unsafe {
+            proc_pidinfo(pid as i32, PROC_PIDT_SHORTBSDINFO, 0, info.as_mut_ptr() as *mut _, size)
+        };
+        if written != size {
+            return None;
+        }
+        let ppid = unsafe { info.assume_init() }.ppid as i64;
+        (ppid > 0).then_some(ppid)
+    }
+
+    /// True if `pid` is this process or one of its descendants (helpers, dev-mode children).
+    fn is_own_process(pid: i64) -> bool {
+        let current_pid = std::process::id() as i64;
+        let mut pid = pid;
+        // A few levels is plenty; launchd (pid 1) ends the walk early for unrelated apps.
+        for _ in 0..8 {
+            if pid == current_pid {
+                return true;
+            }
+            match parent_pid(pid) {
+                Some(parent) if parent > 1 => pid = parent,
+                _ => return false,
+            }
+        }
+        false
+    }
 
-            let iter_elapsed = iter_start.elapsed().as_millis();
-            let elapsed = started_at.elapsed().as_millis();
-            println!(
-                "[rifthold][macos] list_windows total={} fallback_titles={} skipped_layers={} skipped_self={} skipped_control_center={} ids_ms={} desc_ms={} iter_ms={} total_ms={}",
-                entries.len(),
-                fallback_count,
-                skipped_layers,
-                skipped_self,
-                skipped_control_center,
-                ids_elapsed,
-                desc_elapsed,
-                iter_elapsed,
-                elapsed,
+    /// Activating our own overlay would just raise it again; skip it and log the filtering gap.
+    fn refuse_own_window(entry: &MacWindowEntry) -> bool {
+        let own = entry.owner_pid.is_some_and(is_own_process);
+        if own {
+            log::warn!(
+                "[rifthold] refusing to activate own window id={} pid={:?} app={}; self-filter missed it",
+                entry.id, entry.owner_pid, entry.app_name
             );
+        }
+        own
+    }
 
-            // Third pass: capture thumbnails (if enabled)
-            let results: Vec<WindowInfo> = if capture_thumbnails {
-                let thumbnail_start = Instant::now();
-                let max_thumbnail_width = 500; // Max width for thumbnail (increased for better quality)
+    // NSApplicationActivationOptions bits; cocoa models them as an enum, which can't be combined.
+    const ACTIVATE_ALL_WINDOWS: u64 = 1 << 0;
+    const ACTIVATE_IGNORING_OTHER_APPS: u64 = 1 << 1;
 
-                // Use parallel iterator for faster thumbnail capture
-                let results: Vec<WindowInfo> = entries
-                    .par_iter()
-                    .map(|entry| {
-                        let window_id = entry.id.parse::<i64>().unwrap_or(0);
-                        let thumbnail = capture_window_thumbnail(window_id, max_thumbnail_width);
-
-                        WindowInfo {
-                            id: entry.id.clone(),
-                            title: entry.title.clone(),
-                            app_name: entry.app_name.clone(),
-                            is_title_fallback: entry.is_title_fallback,
-                            thumbnail,
-                        }
-                    })
+    /// Activates the app and brings all of its windows above other apps' windows.
+    fn activate_all_windows_via_pid(pid: i64) -> Result<(), String> {
+        unsafe {
+            let app = NSRunningApplication::runningApplicationWithProcessIdentifier(nil, pid as i32);
+            if app == nil {
+                return Err(format!("no running application for pid {pid}"));
+            }
+            let ok: BOOL = msg_send![app, activateWithOptions: ACTIVATE_ALL_WINDOWS | ACTIVATE_IGNORING_OTHER_APPS];
+            if ok == YES {
+                Ok(())
+            } else {
+                Err(format!("NSRunningApplication activate (all windows) failed for pid {pid}"))
+            }
+        }
+    }
+
+    impl WindowProvider for MacWindowProvider {
+        fn list(&self, capture_thumbnails: bool) -> Vec<WindowInfo> {
+            guard_ffi("list", Vec::new(), || {
+                let started_at = Instant::now();
+                // All windows, not only on-screen ones: windows on other Spaces are listed too and
+                // get their last good thumbnail (see `capture_window_thumbnail_or_cached`).
+                let options = kCGWindowListOptionAll | kCGWindowListExcludeDesktopElements;
+                let current_pid = std::process::id() as i64;
+
+                let ids_start = Instant::now();
+                let Some(window_ids) = create_window_list(options, kCGNullWindowID) else {
+                    log::warn!(
+                        "[rifthold][macos] list_windows failed (window ids); elapsed={}ms, falling back to running apps",
+                        started_at.elapsed().as_millis()
+                    );
+                    return self.list_degraded(current_pid);
+                };
+                let ids_elapsed = ids_start.elapsed().as_millis();
+
+                let desc_start = Instant::now();
+                let Some(descriptions) = create_description_from_array(window_ids) else {
+                    log::warn!(
+                        "[rifthold][macos] list_windows failed (descriptions); ids_ms={}, falling back to running apps",
+                        ids_elapsed
+                    );
+                    return self.list_degraded(current_pid);
+                };
+                let desc_elapsed = desc_start.elapsed().as_millis();
+
+                let iter_start = Instant::now();
+                let window_number_key = unsafe { kCGWindowNumber };
+                let owner_name_key = unsafe { kCGWindowOwnerName };
+                let window_name_key = unsafe { kCGWindowName };
+                let owner_pid_key = unsafe { kCGWindowOwnerPID };
+                let layer_key = unsafe { kCGWindowLayer };
+                let bounds_key = unsafe { kCGWindowBounds };
+                let onscreen_key = unsafe { kCGWindowIsOnscreen };
+                let alpha_key = unsafe { kCGWindowAlpha };
+                let require_capturable = super::config().require_capturable;
+                let system_app_exclusions: Vec<String> = super::config()
+                    .system_app_exclusions
+                    .iter()
+                    .map(|name| name.to_lowercase())
                     .collect();
 
-                let thumbnail_elapsed = thumbnail_start.elapsed().as_millis();
-                let total_elapsed = started_at.elapsed().as_millis();
+                let mut fallback_count = 0;
+                let mut skipped_layers = 0;
+                let mut skipped_uncapturable = 0;
+                let mut skipped_self = 0;
+                let mut skipped_system = 0;
+
+                // First pass: collect all window info and identify apps needing title fetch
+                let mut pending_entries = Vec::new();
+                for dict in descriptions.iter() {
+                    let Some(window_number) = number_for_key(&dict, window_number_key) else {
+                        continue;
+                    };
+
+                    let id = window_number.to_string();
+                    let app_name = string_for_key(&dict, owner_name_key)
+                        .unwrap_or_else(|| PLACEHOLDER_APP_NAME.into());
+                    let cg_title = string_for_key(&dict, window_name_key);
+                    let owner_pid = number_for_key(&dict, owner_pid_key);
+                    let layer = number_for_key(&dict, layer_key).unwrap_or(0);
+
+                    if owner_pid == Some(current_pid) {
+                        skipped_self += 1;
+                        continue;
+                    }
+
+                    if layer != 0 {
+                        skipped_layers += 1;
+                        continue;
+                    }
+
+                    if system_app_exclusions.contains(&app_name.to_lowercase()) {
+                        log::debug!("[rifthold][macos] skipped system app {:?} (window {})", app_name, id);
+                        skipped_system += 1;
+                        continue;
+                    }
+
+                    // Fully transparent windows are invisible helpers wherever they are.
+                    if float_for_key(&dict, alpha_key).is_some_and(|alpha| alpha <= 0.0) {
+                        skipped_uncapturable += 1;
+                        continue;
+                    }
+
+                    // Cheap capturability probe: on screen.
+                    let on_screen = bool_for_key(&dict, onscreen_key).unwrap_or(false);
+                    if require_capturable && !on_screen {
+                        skipped_uncapturable += 1;
+                        continue;
+                    }
+
+                    let bounds = bounds_for_key(&dict, bounds_key);
+
+                    pending_entries.push(CgWindow {
+                        id,
+                        app_name,
+                        cg_title,
+                        owner_pid,
+                        layer,
+                        bounds,
+                        on_screen,
+                    });
+                }
+
+                // Second pass: build window entries with CG titles
+                let mut entries = Vec::new();
+                // Bundle ids are resolved once per owning process per refresh.
+                let mut bundle_ids: HashMap<i64, Option<String>> = HashMap::new();
+                let resolve_documents = super::config().resolve_documents;
+                let mut documents: HashMap<i64, HashMap<String, String>> = HashMap::new();
+                let resource_stats = super::config().resource_stats;
+                let self_bundle_id = super::self_bundle_id();
+                let mut resources: HashMap<i64, Option<ProcessResources>> = HashMap::new();
+
+                for CgWindow { id, app_name, cg_title, owner_pid, layer, bounds, on_screen } in pending_entries {
+                    // Use CG title if available (requires Screen Recording permission)
+                    // Otherwise fall back to app name
+                    let (title, is_fallback) = if let Some(t) = cg_title.filter(|t| !t.trim().is_empty()) {
+                        (t, false)
+                    } else {
+                        fallback_count += 1;
+                        (app_name.clone(), true)
+                    };
+
+                    let bundle_id = owner_pid.and_then(|pid| {
+                        bundle_ids
+                            .entry(pid)
+                            .or_insert_with(|| bundle_id_for_pid(pid))
+                            .clone()
+                    });
+
+                    if bundle_id.is_some() && bundle_id == self_bundle_id {
+                        skipped_self += 1;
+                        continue;
+                    }
+
+                    let document_path = match owner_pid {
+                        Some(pid) if resolve_documents && !is_fallback => documents
+                            .entry(pid)
+                            .or_insert_with(|| ax_window_documents(pid as i32))
+                            .get(&title)
+                            .cloned(),
+                        _ => None,
+                    };
+
+                    let process_resources = match owner_pid {
+                        Some(pid) if resource_stats => *resources.entry(pid).or_insert_with(|| sample_process(pid)),
+                        _ => None,
+                    };
+
+                    entries.push(MacWindowEntry {
+                        id,
+                        title,
+                        app_name,
+                        is_title_fallback: is_fallback,
+                        owner_pid,
+                        bundle_id,
+                        layer,
+                        bounds,
+                        on_screen,
+                        document_path,
+                        resources: process_resources,
+                        parent_id: None,
+                        degraded: false,
+                    });
+                }
+
+                if super::config().resolve_parents {
+                    assign_sheet_parents(&mut entries);
+                }
 
-                println!(
-                    "[rifthold][macos] list_windows completed: windows={} thumbnails_captured={} thumbnail_ms={} total_ms={}",
-                    results.len(),
-                    results.iter().filter(|w| w.thumbnail.is_some()).count(),
-                    thumbnail_elapsed,
-                    total_elapsed
+                // Keep the snapshot to resolve activation requests.
+                self.refresh_snapshot(&entries);
+                prune_thumbnail_cache(&entries);
+
+                let iter_elapsed = iter_start.elapsed().as_millis();
+                let elapsed = started_at.elapsed().as_millis();
+                log::debug!(
+                    "[rifthold][macos] list_windows total={} fallback_titles={} skipped_layers={} skipped_self={} skipped_system={} skipped_uncapturable={} ids_ms={} desc_ms={} iter_ms={} total_ms={}",
+                    entries.len(),
+                    fallback_count,
+                    skipped_layers,
+                    skipped_self,
+                    skipped_system,
+                    skipped_uncapturable,
+                    ids_elapsed,
+                    desc_elapsed,
+                    iter_elapsed,
+                    elapsed,
                 );
 
-                results
-            } else {
-                // No thumbnails
-                let results: Vec<WindowInfo> = entries
-                    .into_iter()
-                    .map(|entry| WindowInfo {
-                        id: entry.id,
-                        title: entry.title,
-                        app_name: entry.app_name,
-                        is_title_fallback: entry.is_title_fallback,
-                        thumbnail: None,
-                    })
-                    .collect();
+                // Third pass: capture thumbnails (if enabled)
+                window_infos(entries, capture_thumbnails)
+            })
+        }
 
-                results
-            };
+        fn list_app(&self, bundle_id: &str, capture_thumbnails: bool) -> Vec<WindowInfo> {
+            let mut entries = self.snapshot_entries();
+            if entries.is_empty() {
+                let _ = self.list(false);
+                entries = self.snapshot_entries();
+            }
+
+            let subset: Vec<MacWindowEntry> = entries
+                .into_iter()
+                .filter(|entry| entry.bundle_id.as_deref() == Some(bundle_id))
+                .collect();
+
+            log::debug!(
+                "[rifthold][macos] list_app_windows bundle_id={} windows={}",
+                bundle_id,
+                subset.len()
+            );
 
-            results
+            window_infos(subset, capture_thumbnails)
         }
 
         fn activate(&self, id: &str) -> Result<(), String> {
-            // Try the cached snapshot, then refresh once if missing.
-            let entry = self.find_entry(id).or_else(|| {
-                let _ = self.list(false); // Don't need thumbnails for activation
-                self.find_entry(id)
-            });
-
-            let Some(entry) = entry else {
-                return Err(format!("window id {id} not found"));
-            };
+            let entry = self.resolve_entry(id)?;
+            if refuse_own_window(&entry) {
+                return Ok(());
+            }
 
             // First, activate the application to bring it to the foreground
             let app_activated = if let Some(pid) = entry.owner_pid {
@@ -1030,7 +6534,13 @@ mod macos {
             };
 
             if !app_activated {
-                activate_app(&entry.app_name)?;
+                if activates_by_name(&entry.app_name) {
+                    activate_app(&entry.app_name)?;
+                } else if entry.owner_pid.is_some() {
+                    log::debug!("[rifthold] pid activation failed for unnamed window {}, trying AX raise only", entry.id);
+                } else {
+                    return Err(format!("window {} has neither an app name nor a pid", entry.id));
+                }
             }
 
             // Then, activate the specific window by title using Accessibility API
@@ -1040,8 +6550,9 @@ mod macos {
                     // Give the app a moment to become active
                     std::thread::sleep(std::time::Duration::from_millis(150));
 
-                    if let Err(error) = activate_window_by_title(pid as i32, &entry.title) {
-                        eprintln!("[rifthold] activate_window_by_title failed: {error}");
+                    let bounds = live_window_bounds(&entry);
+                    if let Err(error) = activate_window_by_title(pid as i32, &entry.title, bounds) {
+                        log::warn!("[rifthold] activate_window_by_title failed: {error}");
                     }
                 }
             }
@@ -1049,8 +6560,137 @@ mod macos {
             Ok(())
         }
 
+        fn activate_app(&self, id: &str) -> Result<(), String> {
+            let entry = self.resolve_entry(id)?;
+            if refuse_own_window(&entry) {
+                return Ok(());
+            }
+
+            // No per-window AXRaise: the app's own stacking decides the order of its windows.
+            let activated = match entry.owner_pid {
+                Some(pid) => activate_all_windows_via_pid(pid).is_ok(),
+                None => false,
+            };
+
+            if !activated {
+                if !activates_by_name(&entry.app_name) {
+                    return Err(format!("could not activate unnamed app of window {}", entry.id));
+                }
+                activate_app(&entry.app_name)?;
+            }
+            Ok(())
+        }
+
         fn clear_cache(&self) {
             self.clear_title_cache()
         }
+
+        fn clear_thumbnail_cache(&self) {
+            LAST_GOOD_THUMBNAILS.lock().unwrap().clear();
+        }
+
+        fn clear_snapshot(&self) {
+            *self.snapshot.lock().unwrap() = Snapshot::default();
+        }
+
+        fn window_bounds(&self, id: &str) -> Option<super::WindowBounds> {
+            let dict = window_description(id.parse().ok()?)?;
+            bounds_for_key(&dict, unsafe { kCGWindowBounds })
+        }
+
+        fn launch_app(&self, bundle_id: &str) -> Result<bool, String> {
+            let running = is_bundle_running(bundle_id);
+            let timeout = Duration::from_millis(super::config().open_timeout_ms);
+            let mut open = Command::new("open");
+            open.arg("-b").arg(bundle_id);
+            let status = status_with_timeout(&mut open, timeout)
+                .map_err(|error| format!("launch failed: {error}"))?
+                .ok_or_else(|| format!("open -b {bundle_id} timed out after {timeout:?}"))?;
+            if status.success() {
+                Ok(running)
+            } else {
+                Err(format!("open -b returned status {status:?}"))
+            }
+        }
+
+        fn set_window_frame(&self, id: &str, frame: super::WindowBounds) -> Result<(), String> {
+            let entry = self.resolve_entry(id)?;
+            let pid = entry.owner_pid.ok_or_else(|| format!("window id {id} has no owner pid"))?;
+            set_ax_window_frame(pid as i32, entry.bounds, &entry.title, frame)
+        }
+
+        fn ax_tree(&self, id: &str) -> Result<serde_json::Value, String> {
+            let entry = self.resolve_entry(id)?;
+            let pid = entry.owner_pid.ok_or_else(|| format!("window id {id} has no owner pid"))?;
+            ax_tree(pid as i32, entry.bounds, &entry.title)
+        }
+
+        /// Reads `kCGWindowName` from the single-window description.
+        fn window_title(&self, id: &str) -> Option<String> {
+            let dict = window_description(id.parse().ok()?)?;
+            string_for_key(&dict, unsafe { kCGWindowName })
+        }
+
+        fn app_icon(&self, id: &str) -> Option<String> {
+            let pid = self.resolve_entry(id).ok()?.owner_pid?;
+            app_icon_png(pid).map(|icon| icon.data_url())
+        }
+
+        fn search(&self, query: &str) -> Option<Vec<super::search::Hit>> {
+            Some(self.snapshot.lock().unwrap().search_index.query(query))
+        }
+
+        /// Uses the snapshot (CG z-order, front to back) instead of re-listing. Only on-screen
+        /// windows can be under the point.
+        fn window_at_point(&self, x: f64, y: f64) -> Option<String> {
+            let mut entries = self.snapshot_entries();
+            if entries.is_empty() {
+                let _ = self.list(false);
+                entries = self.snapshot_entries();
+            }
+            entries
+                .into_iter()
+                .find(|entry| entry.on_screen && entry.bounds.is_some_and(|bounds| bounds.contains(x, y)))
+                .map(|entry| entry.id)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn counts() -> (usize, usize) {
+            (
+                ax_ownership::CREATED.with(|created| created.get()),
+                ax_ownership::RELEASED.with(|released| released.get()),
+            )
+        }
+
+        #[test]
+        fn owned_ax_element_releases_each_created_element_once() {
+            {
+                let first = OwnedAXElement::application(42).expect("stub creates an element");
+                let second = OwnedAXElement::application(7).expect("stub creates an element");
+                assert_eq!(first.as_raw(), 42 as AXUIElementRef);
+                drop(second);
+                assert_eq!(counts(), (2, 1));
+            }
+            assert_eq!(counts(), (2, 2));
+        }
+
+        #[test]
+        fn placeholder_app_name_never_activates_by_name() {
+            assert!(!activates_by_name(PLACEHOLDER_APP_NAME));
+            assert!(!activates_by_name(""));
+            assert!(activates_by_name("Safari"));
+            // The guard sits in `activate_app` itself, ahead of any subprocess
+            assert!(activate_app(PLACEHOLDER_APP_NAME).is_err());
+        }
+
+        #[test]
+        fn failed_create_is_never_released() {
+            assert!(OwnedAXElement::application(0).is_none());
+            assert_eq!(counts(), (0, 0));
+        }
     }
 }
@@ -0,0 +1,76 @@
+//! Minimal console logger behind the `log` facade.
+//!
+//! The level is a plain `log::set_max_level` filter so it can be flipped at runtime from the
+//! frontend without relaunching with `RUST_LOG`. The most recent lines are also kept in
+//! memory for diagnostic bundles.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{collections::VecDeque, sync::Mutex};
+
+struct ConsoleLogger;
+
+static LOGGER: ConsoleLogger = ConsoleLogger;
+
+/// How many of the latest log lines `recent_lines` keeps.
+const RECENT_CAPACITY: usize = 500;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = record.args().to_string();
+        match record.level() {
+            Level::Error | Level::Warn => eprintln!("{}", line),
+            _ => println!("{}", line),
+        }
+
+        let mut recent = RECENT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if recent.len() == RECENT_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(format!("{} {}", record.level(), line));
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the logger using `RUST_LOG` (a bare level name) as the initial filter, defaulting to info.
+pub fn init() {
+    let initial = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|value| parse_level(&value))
+        .unwrap_or(LevelFilter::Info);
+
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(initial);
+    }
+}
+
+pub fn parse_level(value: &str) -> Option<LevelFilter> {
+    value.trim().parse::<LevelFilter>().ok()
+}
+
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+pub fn level() -> LevelFilter {
+    log::max_level()
+}
+
+/// The latest logged lines (level-prefixed), oldest first.
+pub fn recent_lines() -> Vec<String> {
+    RECENT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,169 @@
+//! Fuzzy matching for server-side window filtering.
+//!
+//! A query matches when its characters appear in order in the text (case-insensitive).
+//! Consecutive runs and matches at word starts score higher, gaps score lower, so "vsc"
+//! ranks "VS Code" above "Visual Studio Code" above "Devices".
+//!
+//! `SearchIndex` keeps the tokenized titles of the current windows between listings, for
+//! `search_windows`.
+
+use std::collections::{HashMap, HashSet};
+
+/// A successful match: its score, and the matched character indices of the text when they
+/// were asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    pub indices: Vec<usize>,
+}
+
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 12;
+const WORD_START_BONUS: i64 = 8;
+const GAP_PENALTY: i64 = 1;
+
+/// Scores `query` against `text`; `None` if not every query character is found in order.
+/// An empty query matches everything with score 0. `with_indices` also records which
+/// characters of `text` (by char index) matched.
+pub fn score(query: &str, text: &str, with_indices: bool) -> Option<Match> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().collect();
+    let mut indices = Vec::new();
+    let mut total = 0;
+    let mut previous: Option<usize> = None;
+    let mut next = 0;
+
+    for wanted in query {
+        let found = (next..text.len()).find(|&index| text[index].to_lowercase().eq(std::iter::once(wanted)))?;
+        total += MATCH_SCORE;
+        match previous {
+            Some(previous) if previous + 1 == found => total += CONSECUTIVE_BONUS,
+            Some(previous) => total -= GAP_PENALTY * (found - previous - 1) as i64,
+            None => total -= GAP_PENALTY * found as i64,
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            total += WORD_START_BONUS;
+        }
+        if with_indices {
+            indices.push(found);
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+
+    Some(Match { score: total, indices })
+}
+
+/// Merges matched char indices of `text` into half-open `(start, end)` spans, measured in
+/// UTF-16 code units so the frontend can slice JavaScript strings with them directly.
+pub fn match_ranges(text: &str, indices: &[usize]) -> Vec<(usize, usize)> {
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    let mut offset = 0;
+    for c in text.chars() {
+        offsets.push(offset);
+        offset += c.len_utf16();
+    }
+    offsets.push(offset);
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &index in indices {
+        let (start, end) = (offsets[index], offsets[index + 1]);
+        match ranges.last_mut() {
+            Some(last) if last.1 == start => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+/// Bonus when a query term is the prefix of a whole word of the title or app name.
+const TOKEN_PREFIX_BONUS: i64 = 24;
+
+/// Search data of the current windows, kept across listings so that only new or retitled
+/// windows are re-tokenized when the snapshot changes.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    windows: HashMap<String, IndexedWindow>,
+    /// Window ids in snapshot order, the tie-break of `query`.
+    order: Vec<String>,
+}
+
+#[derive(Debug)]
+struct IndexedWindow {
+    title: String,
+    app_name: String,
+    /// Lowercased words of the title and app name.
+    tokens: Vec<String>,
+}
+
+impl IndexedWindow {
+    fn new(title: &str, app_name: &str) -> Self {
+        let tokens = title
+            .split(|c: char| !c.is_alphanumeric())
+            .chain(app_name.split(|c: char| !c.is_alphanumeric()))
+            .filter(|token| !token.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Self {
+            title: title.to_string(),
+            app_name: app_name.to_string(),
+            tokens,
+        }
+    }
+}
+
+/// One window matching a `SearchIndex::query`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub id: String,
+    pub score: i64,
+}
+
+impl SearchIndex {
+    /// Brings the index in line with a snapshot of `(id, title, app name)` triples, dropping
+    /// windows that are gone. Returns how many entries had to be (re)built.
+    pub fn update<'a>(&mut self, windows: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>) -> usize {
+        let mut rebuilt = 0;
+        self.order.clear();
+        for (id, title, app_name) in windows {
+            self.order.push(id.to_string());
+            let current = self
+                .windows
+                .get(id)
+                .is_some_and(|window| window.title == title && window.app_name == app_name);
+            if !current {
+                self.windows.insert(id.to_string(), IndexedWindow::new(title, app_name));
+                rebuilt += 1;
+            }
+        }
+        let live: HashSet<&str> = self.order.iter().map(String::as_str).collect();
+        self.windows.retain(|id, _| live.contains(id.as_str()));
+        rebuilt
+    }
+
+    /// Windows matching every whitespace-separated term of `query` (in the title, else the app
+    /// name), best first; equal scores keep snapshot order. An empty query matches all.
+    pub fn query(&self, query: &str) -> Vec<Hit> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut hits: Vec<Hit> = self
+            .order
+            .iter()
+            .filter_map(|id| {
+                let window = &self.windows[id];
+                let mut total = 0;
+                for term in &terms {
+                    total += match score(term, &window.title, false) {
+                        Some(matched) => matched.score,
+                        None => score(term, &window.app_name, false)?.score,
+                    };
+                    if window.tokens.iter().any(|token| token.starts_with(term.as_str())) {
+                        total += TOKEN_PREFIX_BONUS;
+                    }
+                }
+                Some(Hit { id: id.clone(), score: total })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.cmp(&a.score));
+        hits
+    }
+}
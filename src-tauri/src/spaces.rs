@@ -0,0 +1,196 @@
+//! Spaces (virtual desktops) enumeration and switching.
+//!
+//! macOS has no public API for this. Everything here goes through private CoreGraphics
+//! Services (`CGS*`) symbols, looked up at runtime so a release that renames or removes them
+//! makes the feature report itself unavailable instead of failing to launch. Private API,
+//! may break with any macOS update.
+
+use serde::Serialize;
+
+/// One Space of one display.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SpaceInfo {
+    /// `ManagedSpaceID`, the id `switch_to_space` takes.
+    pub id: i64,
+    /// 1-based position among the Spaces of its display, as in Mission Control.
+    pub index: usize,
+    /// The Space currently shown on its display.
+    pub active: bool,
+    /// UUID of the display the Space belongs to.
+    pub display: String,
+    /// A full-screen app's Space rather than a desktop.
+    pub fullscreen: bool,
+}
+
+/// Whether the private symbols were found on this system.
+pub fn available() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        cgs::symbols().is_some()
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        false
+    }
+}
+
+/// Every Space of every display, in Mission Control order. Empty when unavailable.
+pub fn list_spaces() -> Vec<SpaceInfo> {
+    #[cfg(target_os = "macos")]
+    {
+        cgs::list_spaces()
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        Vec::new()
+    }
+}
+
+/// Makes `space_id` the current Space of its display.
+pub fn switch_to_space(space_id: i64) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        cgs::switch_to_space(space_id)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = space_id;
+        Err("Spaces are only supported on macOS".into())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod cgs {
+    use super::SpaceInfo;
+    use core_foundation::{
+        array::CFArray,
+        base::{CFType, CFTypeRef, TCFType},
+        dictionary::CFDictionary,
+        number::CFNumber,
+        string::{CFString, CFStringRef},
+    };
+    use std::{
+        ffi::{c_char, c_void, CStr},
+        sync::LazyLock,
+    };
+
+    type ConnectionId = i32;
+
+    extern "C" {
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+
+    /// `RTLD_DEFAULT`: search every image already loaded (CoreGraphics re-exports `CGS*`).
+    const RTLD_DEFAULT: *mut c_void = -2isize as *mut c_void;
+
+    pub struct Symbols {
+        main_connection_id: unsafe extern "C" fn() -> ConnectionId,
+        copy_managed_display_spaces: unsafe extern "C" fn(ConnectionId) -> CFTypeRef,
+        set_current_space: unsafe extern "C" fn(ConnectionId, CFStringRef, u64),
+    }
+
+    static SYMBOLS: LazyLock<Option<Symbols>> = LazyLock::new(|| unsafe {
+        let lookup = |name: &CStr| {
+            let symbol = dlsym(RTLD_DEFAULT, name.as_ptr());
+            if symbol.is_null() {
+                log::warn!("[spaces] private symbol {:?} not found; Spaces unavailable", name);
+            }
+            (!symbol.is_null()).then_some(symbol)
+        };
+        let main_connection_id = lookup(c"CGSMainConnectionID")?;
+        let copy_managed_display_spaces = lookup(c"CGSCopyManagedDisplaySpaces")?;
+        let set_current_space = lookup(c"CGSManagedDisplaySetCurrentSpace")?;
+        Some(Symbols {
+            main_connection_id: std::mem::transmute(main_connection_id),
+            copy_managed_display_spaces: std::mem::transmute(copy_managed_display_spaces),
+            set_current_space: std::mem::transmute(set_current_space),
+        })
+    });
+
+    pub fn symbols() -> Option<&'static Symbols> {
+        SYMBOLS.as_ref()
+    }
+
+    type Dict = CFDictionary<CFString, CFType>;
+
+    fn value(dict: &Dict, key: &str) -> Option<CFType> {
+        dict.find(&CFString::new(key)).map(|value| value.clone())
+    }
+
+    fn number(dict: &Dict, key: &str) -> Option<i64> {
+        value(dict, key)?.downcast::<CFNumber>()?.to_i64()
+    }
+
+    fn dict(value: CFType) -> Option<Dict> {
+        value
+            .instance_of::<CFDictionary>()
+            .then(|| unsafe { Dict::wrap_under_get_rule(value.as_CFTypeRef() as _) })
+    }
+
+    fn array(value: CFType) -> Option<CFArray<CFType>> {
+        value
+            .instance_of::<CFArray<CFType>>()
+            .then(|| unsafe { CFArray::wrap_under_get_rule(value.as_CFTypeRef() as _) })
+    }
+
+    /// Space type 4 is a full-screen app; 0 a regular desktop.
+    const SPACE_TYPE_FULLSCREEN: i64 = 4;
+
+    pub fn list_spaces() -> Vec<SpaceInfo> {
+        let Some(symbols) = symbols() else {
+            return Vec::new();
+        };
+        let displays_ref = unsafe { (symbols.copy_managed_display_spaces)((symbols.main_connection_id)()) };
+        if displays_ref.is_null() {
+            return Vec::new();
+        }
+        let displays: CFArray<CFType> = unsafe { CFArray::wrap_under_create_rule(displays_ref as _) };
+
+        let mut spaces = Vec::new();
+        for display in displays.iter().filter_map(|display| dict(display.clone())) {
+            let Some(uuid) = value(&display, "Display Identifier").and_then(|v| v.downcast::<CFString>()) else {
+                continue;
+            };
+            let current = value(&display, "Current Space")
+                .and_then(dict)
+                .and_then(|space| number(&space, "ManagedSpaceID"));
+            let Some(display_spaces) = value(&display, "Spaces").and_then(array) else {
+                continue;
+            };
+            for (position, space) in display_spaces.iter().filter_map(|space| dict(space.clone())).enumerate() {
+                let Some(id) = number(&space, "ManagedSpaceID") else {
+                    continue;
+                };
+                spaces.push(SpaceInfo {
+                    id,
+                    index: position + 1,
+                    active: current == Some(id),
+                    display: uuid.to_string(),
+                    fullscreen: number(&space, "type") == Some(SPACE_TYPE_FULLSCREEN),
+                });
+            }
+        }
+        spaces
+    }
+
+    pub fn switch_to_space(space_id: i64) -> Result<(), String> {
+        let symbols = symbols().ok_or("Spaces API unavailable on this macOS version")?;
+        let space = list_spaces()
+            .into_iter()
+            .find(|space| space.id == space_id)
+            .ok_or_else(|| format!("space {space_id} not found"))?;
+        if space.active {
+            return Ok(());
+        }
+        let display = CFString::new(&space.display);
+        unsafe {
+            (symbols.set_current_space)((symbols.main_connection_id)(), display.as_concrete_TypeRef(), space_id as u64);
+        }
+        log::info!("[spaces] switched display {} to space {} (#{})", space.display, space_id, space.index);
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! Window provider for Wayland compositors implementing `wlr-foreign-toplevel-management`
+//! (Sway and other wlroots compositors).
+//!
+//! The protocol lists toplevels with their title and app id and can activate them, but
+//! exposes no geometry, no stacking order and no pixels: windows come without bounds or
+//! thumbnails (the frontend shows the app icon instead) and are ordered by most recent focus.
+
+use super::{stable_window_key, WindowInfo, WindowProvider};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use wayland_client::{
+    event_created_child,
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_registry::{self, WlRegistry},
+        wl_seat::{self, WlSeat},
+    },
+    Connection, Dispatch, Proxy, QueueHandle,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+struct Toplevel {
+    handle: ZwlrForeignToplevelHandleV1,
+    title: String,
+    app_id: String,
+    /// `Toplevels::activations` when the toplevel last reported focus; 0 if never seen focused.
+    last_activated: u64,
+}
+
+/// Live toplevels keyed by protocol object id, which is also the window id handed out.
+#[derive(Default)]
+struct Toplevels {
+    by_id: HashMap<u32, Toplevel>,
+    /// Focus changes seen so far, to order toplevels by recency.
+    activations: u64,
+}
+
+/// Event-queue state. The queue is dispatched on its own thread; the table is shared with
+/// the provider.
+struct State {
+    toplevels: Arc<Mutex<Toplevels>>,
+}
+
+pub struct WaylandWindowProvider {
+    connection: Connection,
+    seat: WlSeat,
+    toplevels: Arc<Mutex<Toplevels>>,
+}
+
+impl WaylandWindowProvider {
+    /// Connects to `$WAYLAND_DISPLAY` and binds the toplevel manager. Fails when the
+    /// compositor does not advertise the protocol or a seat to activate with.
+    pub fn connect() -> Result<Self, String> {
+        let connection = Connection::connect_to_env().map_err(|e| e.to_string())?;
+        let (globals, mut queue) = registry_queue_init::<State>(&connection).map_err(|e| e.to_string())?;
+        let queue_handle = queue.handle();
+        globals
+            .bind::<ZwlrForeignToplevelManagerV1, _, _>(&queue_handle, 1..=3, ())
+            .map_err(|e| format!("wlr-foreign-toplevel-management unavailable: {e}"))?;
+        let seat = globals
+            .bind::<WlSeat, _, _>(&queue_handle, 1..=1, ())
+            .map_err(|e| format!("no wl_seat: {e}"))?;
+
+        let toplevels = Arc::new(Mutex::new(Toplevels::default()));
+        let mut state = State {
+            toplevels: Arc::clone(&toplevels),
+        };
+        // The first roundtrip announces the existing toplevels, the second their titles/app ids.
+        for _ in 0..2 {
+            queue.roundtrip(&mut state).map_err(|e| e.to_string())?;
+        }
+        log::info!("[rifthold][wayland] tracking {} toplevels", toplevels.lock().unwrap().by_id.len());
+
+        std::thread::Builder::new()
+            .name("wayland-toplevels".into())
+            .spawn(move || loop {
+                if let Err(error) = queue.blocking_dispatch(&mut state) {
+                    log::warn!("[rifthold][wayland] event queue stopped: {}", error);
+                    break;
+                }
+            })
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            connection,
+            seat,
+            toplevels,
+        })
+    }
+
+    fn handle(&self, id: &str) -> Result<ZwlrForeignToplevelHandleV1, String> {
+        id.parse::<u32>()
+            .ok()
+            .and_then(|id| self.toplevels.lock().unwrap().by_id.get(&id).map(|toplevel| toplevel.handle.clone()))
+            .ok_or_else(|| format!("window {id} not found"))
+    }
+}
+
+fn window_info(id: u32, toplevel: &Toplevel) -> WindowInfo {
+    let is_title_fallback = toplevel.title.is_empty();
+    let title = if is_title_fallback {
+        toplevel.app_id.clone()
+    } else {
+        toplevel.title.clone()
+    };
+    WindowInfo {
+        id: id.to_string(),
+        stable_key: stable_window_key(&toplevel.app_id, "window", &title),
+        title,
+        app_name: toplevel.app_id.clone(),
+        is_title_fallback,
+        ..Default::default()
+    }
+}
+
+impl WindowProvider for WaylandWindowProvider {
+    fn list(&self, _capture_thumbnails: bool) -> Vec<WindowInfo> {
+        let toplevels = self.toplevels.lock().unwrap();
+        let mut entries: Vec<(&u32, &Toplevel)> = toplevels.by_id.iter().collect();
+        entries.sort_by(|a, b| b.1.last_activated.cmp(&a.1.last_activated).then(a.0.cmp(b.0)));
+        entries.into_iter().map(|(id, toplevel)| window_info(*id, toplevel)).collect()
+    }
+
+    /// App ids stand in for bundle ids.
+    fn list_app(&self, bundle_id: &str, capture_thumbnails: bool) -> Vec<WindowInfo> {
+        self.list(capture_thumbnails)
+            .into_iter()
+            .filter(|window| window.app_name == bundle_id)
+            .collect()
+    }
+
+    fn activate(&self, id: &str) -> Result<(), String> {
+        self.handle(id)?.activate(&self.seat);
+        self.connection.flush().map_err(|e| e.to_string())
+    }
+
+    /// The protocol has no app-level activation: activates every toplevel of the app, `id` last
+    /// so that it ends up focused.
+    fn activate_app(&self, id: &str) -> Result<(), String> {
+        let target = self.handle(id)?;
+        let siblings: Vec<ZwlrForeignToplevelHandleV1> = {
+            let toplevels = self.toplevels.lock().unwrap();
+            let app_id = toplevels
+                .by_id
+                .get(&target.id().protocol_id())
+                .map(|toplevel| toplevel.app_id.clone())
+                .unwrap_or_default();
+            toplevels
+                .by_id
+                .values()
+                .filter(|toplevel| toplevel.app_id == app_id && toplevel.handle != target)
+                .map(|toplevel| toplevel.handle.clone())
+                .collect()
+        };
+        for handle in siblings.iter().chain(std::iter::once(&target)) {
+            handle.activate(&self.seat);
+        }
+        self.connection.flush().map_err(|e| e.to_string())
+    }
+
+    fn clear_cache(&self) {
+        // Nothing cached: the table is kept current by compositor events.
+    }
+
+    fn window_title(&self, id: &str) -> Option<String> {
+        let id = id.parse::<u32>().ok()?;
+        self.toplevels.lock().unwrap().by_id.get(&id).map(|toplevel| toplevel.title.clone())
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(_: &mut Self, _: &WlRegistry, _: wl_registry::Event, _: &GlobalListContents, _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(_: &mut Self, _: &WlSeat, _: wl_seat::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } => {
+                state.toplevels.lock().unwrap().by_id.insert(
+                    toplevel.id().protocol_id(),
+                    Toplevel {
+                        handle: toplevel,
+                        title: String::new(),
+                        app_id: String::new(),
+                        last_activated: 0,
+                    },
+                );
+            }
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                log::warn!("[rifthold][wayland] compositor stopped sending toplevel updates");
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let id = handle.id().protocol_id();
+        let mut toplevels = state.toplevels.lock().unwrap();
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => {
+                if let Some(toplevel) = toplevels.by_id.get_mut(&id) {
+                    toplevel.title = title;
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => {
+                if let Some(toplevel) = toplevels.by_id.get_mut(&id) {
+                    toplevel.app_id = app_id;
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state: flags } => {
+                // An array of native-endian u32 state values.
+                let activated = flags.chunks_exact(4).any(|value| {
+                    u32::from_ne_bytes([value[0], value[1], value[2], value[3]])
+                        == zwlr_foreign_toplevel_handle_v1::State::Activated as u32
+                });
+                if activated {
+                    toplevels.activations += 1;
+                    let stamp = toplevels.activations;
+                    if let Some(toplevel) = toplevels.by_id.get_mut(&id) {
+                        toplevel.last_activated = stamp;
+                    }
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                toplevels.by_id.remove(&id);
+                handle.destroy();
+            }
+            _ => {}
+        }
+    }
+}
@@ -7,7 +7,7 @@ import {
   memo,
   type SVGProps,
 } from "react";
-import { invoke } from "@tauri-apps/api/core";
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
 
 type ColorMode = "system" | "light" | "dark";
 
@@ -131,12 +131,67 @@ function ColorModeToggle({ colorMode, setColorMode }: { colorMode: ColorMode; se
   );
 }
 
+// Mirrors the backend `CommandError` envelope returned by failing commands
+type CommandError = {
+  code: string;
+  message: string;
+  details?: unknown;
+};
+
+const describeError = (error: unknown) =>
+  typeof error === "object" && error !== null && "message" in error
+    ? (error as CommandError).message
+    : String(error);
+
 type WindowInfo = {
   id: string;
   title: string;
   appName: string;
   isTitleFallback?: boolean;
   thumbnail?: string;
+  thumbnailStale?: boolean;
+  stableKey?: string;
+  rawTitle?: string;
+  documentPath?: string;
+  parentId?: string;
+  degraded?: boolean;
+  titleDisambiguator?: string;
+  displayId?: number;
+  matchRanges?: [number, number][];
+  flagged?: boolean;
+  cornerGlyph?: string;
+  appWindowCount?: number;
+  stale?: boolean;
+};
+
+type BundledThumbnail = { id: string; url: string; stale: boolean };
+
+// Parses a `bundle_thumbnails` buffer (layout documented at `encode_thumbnail_bundle` in lib.rs)
+// into object URLs, one per window
+const parseThumbnailBundle = (buffer: ArrayBuffer): BundledThumbnail[] => {
+  const view = new DataView(buffer);
+  const magic = String.fromCharCode(...new Uint8Array(buffer, 0, 4));
+  if (magic !== "RTHB" || view.getUint32(4, true) !== 1) {
+    throw new Error("unsupported thumbnail bundle");
+  }
+  const count = view.getUint32(8, true);
+  const decoder = new TextDecoder();
+  const thumbnails: BundledThumbnail[] = [];
+  let cursor = 12;
+  for (let i = 0; i < count; i++) {
+    const idLength = view.getUint16(cursor, true);
+    const id = decoder.decode(new Uint8Array(buffer, cursor + 2, idLength));
+    cursor += 2 + idLength;
+    const offset = view.getUint32(cursor, true);
+    const length = view.getUint32(cursor + 4, true);
+    const flags = view.getUint8(cursor + 8);
+    cursor += 9;
+    const blob = new Blob([new Uint8Array(buffer, offset, length)], {
+      type: flags & 2 ? "image/png" : "image/jpeg",
+    });
+    thumbnails.push({ id, url: URL.createObjectURL(blob), stale: (flags & 1) !== 0 });
+  }
+  return thumbnails;
 };
 
 const MOCK_WINDOWS: WindowInfo[] = [
@@ -171,6 +226,8 @@ type WindowCardProps = {
   index: number;
   onSelect: () => void;
   onActivate: () => void;
+  /** Lazy-thumbnail mode: called once the tile scrolls into view. */
+  onVisible?: (id: string) => void;
 };
 
 const WindowCard = memo(function WindowCard({
@@ -179,13 +236,29 @@ const WindowCard = memo(function WindowCard({
   index,
   onSelect,
   onActivate,
+  onVisible,
 }: WindowCardProps) {
   const displayTitle = windowInfo.title || windowInfo.appName;
   const gradient = gradientForIndex(index);
   const hasThumbnail = !!windowInfo.thumbnail;
+  const cardRef = useRef<HTMLButtonElement>(null);
+
+  useEffect(() => {
+    const node = cardRef.current;
+    if (!onVisible || !node) return;
+    const observer = new IntersectionObserver((entries) => {
+      if (entries.some((entry) => entry.isIntersecting)) {
+        onVisible(windowInfo.id);
+        observer.disconnect();
+      }
+    });
+    observer.observe(node);
+    return () => observer.disconnect();
+  }, [onVisible, windowInfo.id]);
 
   return (
     <button
+      ref={cardRef}
       type="button"
       onClick={onSelect}
       onDoubleClick={onActivate}
@@ -239,12 +312,28 @@ const WindowCard = memo(function WindowCard({
           >
             {displayTitle}
           </p>
-          <p className="truncate text-sm text-muted-foreground">
-            {windowInfo.appName}
+          <p className="flex items-center gap-1.5 text-sm text-muted-foreground">
+            {windowInfo.cornerGlyph && (
+              <img src={windowInfo.cornerGlyph} alt="" className="h-4 w-4 shrink-0 rounded-sm object-cover" />
+            )}
+            <span className="truncate">
+              {windowInfo.appName}
+              {windowInfo.titleDisambiguator && ` · ${windowInfo.titleDisambiguator}`}
+            </span>
           </p>
         </div>
         <div className="flex items-center justify-between text-xs text-muted-foreground">
-          <span className="rounded-full bg-muted px-2 py-1">⌘{index + 1}</span>
+          <span className="flex items-center gap-1">
+            <span className="rounded-full bg-muted px-2 py-1">⌘{index + 1}</span>
+            {windowInfo.flagged && (
+              <span className="rounded-full bg-primary px-2 py-1 text-[10px] font-semibold text-primary-foreground">
+                Attention
+              </span>
+            )}
+            {(windowInfo.appWindowCount ?? 1) > 1 && (
+              <span className="rounded-full bg-muted px-2 py-1">{windowInfo.appWindowCount} windows</span>
+            )}
+          </span>
           <span className="hidden items-center gap-1 rounded-full border border-border px-2 py-1 text-[10px] font-semibold uppercase tracking-[0.16em] sm:flex">
             Enter to switch
           </span>
@@ -280,6 +369,12 @@ function App() {
   const [selectedIndex, setSelectedIndex] = useState(0);
   const [isLoading, setIsLoading] = useState(false);
   const [loadingThumbnails, setLoadingThumbnails] = useState(false);
+  const [lazyThumbnails, setLazyThumbnails] = useState(false);
+  const requestedThumbnailsRef = useRef(new Set<string>());
+  const pendingThumbnailsRef = useRef<string[]>([]);
+  const bundleUrlsRef = useRef(new Map<string, string>());
+  // Newest thumbnail version applied per window; older captures arriving late are dropped
+  const thumbnailVersionsRef = useRef(new Map<string, number>());
   const [showSettings, setShowSettings] = useState(false);
   const [shortcut, setShortcut] = useState("alt+space");
   const [editingShortcut, setEditingShortcut] = useState("");
@@ -299,7 +394,9 @@ function App() {
   // Save to cache whenever windows update
   useEffect(() => {
     try {
-      localStorage.setItem(CACHE_KEY, JSON.stringify(windows));
+      // Object URLs from a thumbnail bundle do not survive a reload
+      const cacheable = windows.map(w => (w.thumbnail?.startsWith("blob:") ? { ...w, thumbnail: undefined } : w));
+      localStorage.setItem(CACHE_KEY, JSON.stringify(cacheable));
       console.log("[cache] saved", windows.length, "windows to cache");
     } catch (error) {
       console.warn("[cache] failed to save", error);
@@ -326,9 +423,10 @@ function App() {
     if (!normalizedQuery) return windows;
     const terms = normalizedQuery.split(/\s+/);
     return windows.filter((windowInfo) => {
-      const title = windowInfo.title.toLowerCase();
+      const title = (windowInfo.rawTitle ?? windowInfo.title).toLowerCase();
       const app = windowInfo.appName.toLowerCase();
-      return terms.every(term => app.includes(term) || title.includes(term));
+      const documentPath = windowInfo.documentPath?.toLowerCase() ?? "";
+      return terms.every(term => app.includes(term) || title.includes(term) || documentPath.includes(term));
     });
   }, [normalizedQuery, windows]);
 
@@ -344,6 +442,8 @@ function App() {
       // Listen for window list
       const unlistenList = await listen<WindowInfo[]>("windows:list", (event) => {
         console.log("[event] received window list:", event.payload.length, "windows");
+        setLazyThumbnails(false);
+        requestedThumbnailsRef.current.clear();
 
         // Merge with existing thumbnails from cache
         setWindows(prev => {
@@ -365,31 +465,99 @@ function App() {
       });
 
       // Listen for individual thumbnails
-      const unlistenThumbnail = await listen<{ id: string; thumbnail: string }>("window:thumbnail", (event) => {
+      const unlistenThumbnail = await listen<{ id: string; thumbnail: string; stale?: boolean; cornerGlyph?: string; version?: number }>("window:thumbnail", (event) => {
         console.log("[event] received thumbnail for window:", event.payload.id);
+        const { version } = event.payload;
+        if (version !== undefined) {
+          const applied = thumbnailVersionsRef.current.get(event.payload.id);
+          if (applied !== undefined && version < applied) {
+            console.log("[event] dropped out-of-order thumbnail for window:", event.payload.id);
+            return;
+          }
+          thumbnailVersionsRef.current.set(event.payload.id, version);
+        }
         setWindows(prev =>
           prev.map(w =>
-            w.id === event.payload.id ? { ...w, thumbnail: event.payload.thumbnail } : w
+            w.id === event.payload.id
+              ? {
+                  ...w,
+                  thumbnail: event.payload.thumbnail,
+                  thumbnailStale: !!event.payload.stale,
+                  cornerGlyph: event.payload.cornerGlyph ?? w.cornerGlyph,
+                }
+              : w
           )
         );
       });
 
+      // Bundled mode: every thumbnail of the refresh in one binary response
+      const unlistenBundle = await listen<{ generation: number; count: number; cornerGlyphs?: Record<string, string> }>("windows:thumbnails-bundle", async (event) => {
+        try {
+          const response = await fetch(convertFileSrc(`thumbnails-${event.payload.generation}`, "rifthold"));
+          if (!response.ok) return; // superseded by a newer refresh
+          const thumbnails = parseThumbnailBundle(await response.arrayBuffer());
+          const byId = new Map(thumbnails.map(t => [t.id, t]));
+          setWindows(prev =>
+            prev.map(w => {
+              const bundled = byId.get(w.id);
+              const cornerGlyph = event.payload.cornerGlyphs?.[w.id] ?? w.cornerGlyph;
+              return bundled ? { ...w, thumbnail: bundled.url, thumbnailStale: bundled.stale, cornerGlyph } : w;
+            })
+          );
+          // Release the object URLs these replaced; windows missing from the bundle keep theirs
+          for (const { id, url } of thumbnails) {
+            const previous = bundleUrlsRef.current.get(id);
+            if (previous) URL.revokeObjectURL(previous);
+            bundleUrlsRef.current.set(id, url);
+          }
+        } catch (error) {
+          console.warn("[event] failed to load thumbnail bundle", error);
+        }
+      });
+
+      // Live titles: a listed window's title changed while the overlay is open
+      const unlistenTitle = await listen<{ id: string; title: string }>("window:title-changed", (event) => {
+        setWindows(prev =>
+          prev.map(w => (w.id === event.payload.id ? { ...w, title: event.payload.title } : w))
+        );
+      });
+
+      // External tooling flagged a window as needing attention
+      const unlistenFlagged = await listen<{ id: string; flagged: boolean }>("window:flagged", (event) => {
+        setWindows(prev =>
+          prev.map(w => (w.id === event.payload.id ? { ...w, flagged: event.payload.flagged } : w))
+        );
+      });
+
       // Listen for thumbnails complete
       const unlistenComplete = await listen("windows:thumbnails-complete", () => {
         console.log("[event] all thumbnails loaded");
         setLoadingThumbnails(false);
       });
 
-      // Trigger initial load (non-blocking)
-      console.log("[mount] triggering background refresh");
-      invoke("refresh_windows_async").catch(error => {
-        console.warn("[mount] refresh failed", error);
+      // Lazy mode: the backend skipped capturing, tiles request thumbnails as they appear
+      const unlistenDeferred = await listen("windows:thumbnails-deferred", () => {
+        console.log("[event] thumbnails deferred to visible tiles");
+        setLazyThumbnails(true);
+        setLoadingThumbnails(false);
       });
 
+      // Trigger initial load (non-blocking) once the backend finished setup
+      console.log("[mount] triggering background refresh");
+      invoke("wait_for_ready")
+        .then(() => invoke("refresh_windows_async", { forceThumbnails: true }))
+        .catch(error => {
+          console.warn("[mount] refresh failed", error);
+        });
+
       return () => {
         unlistenList();
         unlistenThumbnail();
+        unlistenBundle();
+        unlistenTitle();
+        unlistenFlagged();
         unlistenComplete();
+        unlistenDeferred();
       };
     };
 
@@ -432,6 +600,19 @@ function App() {
     };
   }, [resetOverlayState]);
 
+  // Batch visibility callbacks from one layout pass into a single backend request
+  const requestVisibleThumbnail = useCallback((id: string) => {
+    if (requestedThumbnailsRef.current.has(id)) return;
+    requestedThumbnailsRef.current.add(id);
+    pendingThumbnailsRef.current.push(id);
+    if (pendingThumbnailsRef.current.length > 1) return;
+    requestAnimationFrame(() => {
+      const ids = pendingThumbnailsRef.current;
+      pendingThumbnailsRef.current = [];
+      invoke("request_thumbnails_in_range", { ids }).catch(console.warn);
+    });
+  }, []);
+
   useEffect(() => {
     if (filteredWindows.length === 0) {
       setSelectedIndex(-1);
@@ -461,10 +642,14 @@ function App() {
 
   const hideOverlay = useCallback(async () => {
     try {
-      const windowInstance = getCurrentWindow();
-      await windowInstance.hide();
+      await invoke("hide_overlay");
     } catch (error) {
-      console.warn("hide window failed", error);
+      console.warn("hide_overlay failed, hiding from webview", error);
+      try {
+        await getCurrentWindow().hide();
+      } catch (hideError) {
+        console.warn("hide window failed", hideError);
+      }
     }
   }, []);
 
@@ -606,6 +791,18 @@ function App() {
           </div>
         )}
 
+        {windows.some((w) => w.degraded) && (
+          <div className="flex items-start gap-3 rounded-2xl border border-amber-400/30 bg-amber-500/10 px-4 py-3 text-sm text-amber-100">
+            <div className="mt-[2px] h-2 w-2 rounded-full bg-amber-300" />
+            <div className="space-y-1">
+              <p className="font-semibold">Showing apps instead of windows</p>
+              <p className="text-amber-100/80">
+                Window enumeration failed, so each running app is listed once. Selecting one switches to that app.
+              </p>
+            </div>
+          </div>
+        )}
+
         <div className="flex flex-col gap-2">
           <div className="flex items-center gap-3 rounded-2xl border border-border bg-card px-4 py-3 shadow-lg focus-within:border-ring">
             <SearchIcon className="h-5 w-5 text-muted-foreground" />
@@ -657,6 +854,7 @@ function App() {
                 index={index}
                 onSelect={() => setSelectedIndex(index)}
                 onActivate={() => activateWindow(windowInfo)}
+                onVisible={lazyThumbnails ? requestVisibleThumbnail : undefined}
               />
             );
           })}
@@ -750,7 +948,7 @@ function App() {
                       setShortcut(editingShortcut);
                       setShowSettings(false);
                     } catch (error) {
-                      alert(`Failed to set shortcut: ${error}`);
+                      alert(`Failed to set shortcut: ${describeError(error)}`);
                     }
                   }}
                   className="flex-1 rounded-lg bg-primary px-4 py-2 text-sm font-semibold text-primary-foreground transition hover:opacity-90"